@@ -0,0 +1,378 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::{code_lines, Span};
+use std::collections::HashMap;
+
+// Hack assembly to machine code, one 16-bit word per instruction:
+//
+// @value        0vvv vvvv vvvv vvvv
+// dest=comp;jmp 111a cccc ccdd djjj
+//
+// Labels are declared with `(LABEL)` and name the address of the next
+// instruction. Any other symbol is a variable, given the next free RAM
+// address from 16 up.
+
+/// Instructions that fit in the ROM.
+pub const ROM_SIZE: usize = 32768;
+
+const FIRST_VARIABLE: u16 = 16;
+
+/// Symbols every program has. The first name of an address is the one the
+/// disassembler uses.
+pub const PREDEFINED: &[(&str, u16)] = &[
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
+/// The symbols a program defined, saved next to its machine code so that the
+/// disassembler can name addresses again. As text, one symbol per line:
+///
+/// ```text
+/// label 12 LOOP
+/// variable 16 i
+/// ```
+#[derive(Debug, Default)]
+pub struct SymbolMap {
+    /// Labels and the ROM address they name, in address order
+    pub labels: Vec<(String, u16)>,
+    /// Variables and the RAM address they were given
+    pub variables: Vec<(String, u16)>,
+}
+
+impl SymbolMap {
+    pub fn parse(source: &str) -> Result<SymbolMap, Box<Diagnostic>> {
+        let mut map = SymbolMap::default();
+        for (code, span) in code_lines(source) {
+            let words: Vec<&str> = code.split_whitespace().collect();
+            let symbol = match words.as_slice() {
+                [kind, address, name] if is_symbol(name) => address.parse::<u16>().ok().map(|address| (*kind, (name.to_string(), address))),
+                _ => None,
+            };
+            match symbol {
+                Some(("label", symbol)) => map.labels.push(symbol),
+                Some(("variable", symbol)) => map.variables.push(symbol),
+                _ => {
+                    return Err(Diagnostic::error(codes::INVALID_SYMBOL, format!("invalid symbol map entry `{}`", code), span)
+                        .with_help("entries are `label <address> <name>` or `variable <address> <name>`")
+                        .into());
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn to_text(&self) -> String {
+        let labels = self.labels.iter().map(|(name, address)| format!("label {} {}\n", address, name));
+        let variables = self.variables.iter().map(|(name, address)| format!("variable {} {}\n", address, name));
+        labels.chain(variables).collect()
+    }
+}
+
+/// Computations and their ALU control bits, with `A` standing for `M` too
+/// when the `a` bit is set.
+pub const COMPUTATIONS: &[(&str, u16)] = &[
+    ("0", 0b101010),
+    ("1", 0b111111),
+    ("-1", 0b111010),
+    ("D", 0b001100),
+    ("A", 0b110000),
+    ("!D", 0b001101),
+    ("!A", 0b110001),
+    ("-D", 0b001111),
+    ("-A", 0b110011),
+    ("D+1", 0b011111),
+    ("A+1", 0b110111),
+    ("D-1", 0b001110),
+    ("A-1", 0b110010),
+    ("D+A", 0b000010),
+    ("D-A", 0b010011),
+    ("A-D", 0b000111),
+    ("D&A", 0b000000),
+    ("D|A", 0b010101),
+];
+
+/// Jump mnemonics, indexed by their bits.
+pub const JUMPS: [&str; 8] = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+/// Destinations, indexed by their bits.
+pub const DESTINATIONS: [&str; 8] = ["", "M", "D", "MD", "A", "AM", "AD", "AMD"];
+
+enum Instruction {
+    Address(u16),
+    Symbol(String),
+    Compute(u16),
+}
+
+/// Assembles `source` into machine code and the symbols it defines, or every
+/// error found in it.
+pub fn assemble(source: &str) -> Result<(Vec<u16>, SymbolMap), Vec<Diagnostic>> {
+    let mut symbols: HashMap<String, u16> = PREDEFINED.iter().map(|&(name, address)| (name.to_string(), address)).collect();
+    let mut symbol_map = SymbolMap::default();
+    let mut instructions = Vec::new();
+    let mut errors = Vec::new();
+
+    // First pass: decode instructions and give labels the address they precede
+    for (code, span) in code_lines(source) {
+        if let Some(label) = code.strip_prefix('(') {
+            let Some(label) = label.strip_suffix(')').filter(|label| is_symbol(label)) else {
+                errors.push(Diagnostic::error(codes::INVALID_SYMBOL, format!("invalid label declaration `{}`", code), span));
+                continue;
+            };
+            if symbols.contains_key(label) {
+                errors.push(Diagnostic::error(codes::DUPLICATE_LABEL, format!("the symbol `{}` is already defined", label), span));
+            } else {
+                // Past the end of the ROM only fails once an instruction is there
+                let address = instructions.len().min(ROM_SIZE) as u16;
+                symbols.insert(label.to_string(), address);
+                symbol_map.labels.push((label.to_string(), address));
+            }
+            continue;
+        }
+
+        let instruction = match code.strip_prefix('@') {
+            Some(value) => address(value, span),
+            None => compute(code, span).map(Instruction::Compute),
+        };
+        match instruction {
+            Ok(instruction) => instructions.push((instruction, span)),
+            Err(error) => errors.push(*error),
+        }
+    }
+    if instructions.len() > ROM_SIZE {
+        let (_, span) = instructions[ROM_SIZE];
+        errors.push(
+            Diagnostic::error(codes::ROM_OVERFLOW, "the program does not fit in the ROM", span)
+                .with_note(format!("the ROM holds {} instructions, the program has {}", ROM_SIZE, instructions.len())),
+        );
+    }
+    if !errors.is_empty() {
+        errors.sort_by_key(|error| error.span.start);
+        return Err(errors);
+    }
+
+    // Second pass: resolve symbols, allocating variables as they are first seen
+    let mut next_variable = FIRST_VARIABLE;
+    let words = instructions
+        .into_iter()
+        .map(|(instruction, _)| match instruction {
+            Instruction::Address(address) => address,
+            Instruction::Symbol(symbol) => *symbols.entry(symbol).or_insert_with_key(|symbol| {
+                symbol_map.variables.push((symbol.clone(), next_variable));
+                next_variable += 1;
+                next_variable - 1
+            }),
+            Instruction::Compute(word) => word,
+        })
+        .collect();
+    Ok((words, symbol_map))
+}
+
+fn address(value: &str, span: Span) -> Result<Instruction, Box<Diagnostic>> {
+    if value.starts_with(|c: char| c.is_ascii_digit()) {
+        return value.parse::<u16>().ok().filter(|address| *address < 0x8000).map(Instruction::Address).ok_or_else(|| {
+            Diagnostic::error(codes::INVALID_SYMBOL, format!("`{}` is not an address from 0 to 32767", value), span).into()
+        });
+    }
+    if !is_symbol(value) {
+        return Err(Diagnostic::error(codes::INVALID_SYMBOL, format!("`{}` is not a valid symbol", value), span).into());
+    }
+    Ok(Instruction::Symbol(value.to_string()))
+}
+
+// Letters, digits and `_.$:`, not starting with a digit
+fn is_symbol(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c))
+}
+
+// Encodes `dest=comp;jump`, where `dest=` and `;jump` are optional
+fn compute(code: &str, span: Span) -> Result<u16, Box<Diagnostic>> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let (dest, rest) = code.split_once('=').unwrap_or(("", &code));
+    let (comp, jump) = rest.split_once(';').unwrap_or((rest, ""));
+    let unknown = |kind: &str, mnemonic: &str| Diagnostic::error(codes::UNKNOWN_MNEMONIC, format!("unknown {} `{}`", kind, mnemonic), span);
+
+    let dest_bits = dest_bits(dest).ok_or_else(|| unknown("destination", dest).with_help("destinations are made of the registers A, D and M"))?;
+    let comp_bits = comp_bits(comp).ok_or_else(|| unknown("computation", comp))?;
+    let Some(jump_bits) = JUMPS.iter().position(|name| *name == jump) else {
+        return Err(unknown("jump", jump).with_help("the jumps are JGT, JEQ, JGE, JLT, JNE, JLE and JMP").into());
+    };
+    Ok(0b111 << 13 | comp_bits << 6 | dest_bits << 3 | jump_bits as u16)
+}
+
+// Any order of A, D and M, each at most once
+fn dest_bits(dest: &str) -> Option<u16> {
+    let mut bits = 0;
+    for register in dest.chars() {
+        let bit = match register {
+            'A' => 0b100,
+            'D' => 0b010,
+            'M' => 0b001,
+            _ => return None,
+        };
+        if bits & bit != 0 {
+            return None;
+        }
+        bits |= bit;
+    }
+    Some(bits)
+}
+
+// The `a` bit and the six ALU control bits. Commutative operations are
+// accepted with their operands either way round.
+fn comp_bits(comp: &str) -> Option<u16> {
+    let (a, comp) = if comp.contains('M') { (1, comp.replace('M', "A")) } else { (0, comp.to_string()) };
+    let lookup = |comp: &str| COMPUTATIONS.iter().find(|(name, _)| *name == comp).map(|&(_, bits)| bits);
+    let swapped = comp.find(['+', '&', '|']).map(|i| format!("{}{}{}", &comp[i + 1..], &comp[i..=i], &comp[..i]));
+    let bits = lookup(&comp).or_else(|| swapped.and_then(|comp| lookup(&comp)))?;
+    Some(a << 6 | bits)
+}
+
+/// Reads the machine code of a `.hack` file, one binary word per line.
+pub fn parse_hack(source: &str) -> Result<Vec<u16>, Box<Diagnostic>> {
+    let lines = code_lines(source);
+    if let Some((_, span)) = lines.get(ROM_SIZE) {
+        return Err(Diagnostic::error(codes::ROM_OVERFLOW, "the program does not fit in the ROM", *span)
+            .with_note(format!("the ROM holds {} instructions, the program has {}", ROM_SIZE, lines.len()))
+            .into());
+    }
+    lines
+        .into_iter()
+        .map(|(code, span)| {
+            let valid = code.len() == 16 && code.chars().all(|c| c == '0' || c == '1');
+            valid.then(|| u16::from_str_radix(code, 2).expect("16 binary digits")).ok_or_else(|| {
+                Diagnostic::error(codes::INVALID_MACHINE_CODE, format!("expected a word of 16 binary digits, found `{}`", code), span).into()
+            })
+        })
+        .collect()
+}
+
+/// The `.hack` text of `words`, one binary word per line.
+pub fn to_text(words: &[u16]) -> String {
+    words.iter().map(|word| format!("{:016b}\n", word)).collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const ADD: &str = "\
+// RAM[0] = 2 + 3
+@2
+D=A
+@3
+D=D+A
+@0
+M=D
+";
+
+    /// A Hack CPU, to run what the translator and the assembler produce. Runs
+    /// `rom` from address 0 for `steps` instructions and returns the RAM.
+    pub(crate) fn run_hack(rom: &[u16], steps: usize) -> Vec<i16> {
+        let mut ram = vec![0i16; 32768];
+        let (mut a, mut d, mut pc) = (0i16, 0i16, 0usize);
+        for _ in 0..steps {
+            let Some(&word) = rom.get(pc) else { break };
+            if word & 0x8000 == 0 {
+                a = word as i16;
+                pc += 1;
+                continue;
+            }
+            let mut x = d;
+            let mut y = if word & 0x1000 == 0 { a } else { ram[a as u16 as usize] };
+            let bit = |n: u16| word & (1 << n) != 0;
+            if bit(11) {
+                x = 0;
+            }
+            if bit(10) {
+                x = !x;
+            }
+            if bit(9) {
+                y = 0;
+            }
+            if bit(8) {
+                y = !y;
+            }
+            let mut out = if bit(7) { x.wrapping_add(y) } else { x & y };
+            if bit(6) {
+                out = !out;
+            }
+            let address = a as u16 as usize;
+            if bit(3) {
+                ram[address] = out;
+            }
+            if bit(5) {
+                a = out;
+            }
+            if bit(4) {
+                d = out;
+            }
+            let jump = (bit(2) && out < 0) || (bit(1) && out == 0) || (bit(0) && out > 0);
+            pc = if jump { a as u16 as usize } else { pc + 1 };
+        }
+        ram
+    }
+
+    #[test]
+    fn assembles_the_add_program() {
+        let (words, _) = assemble(ADD).unwrap();
+        assert_eq!(
+            to_text(&words),
+            "0000000000000010\n1110110000010000\n0000000000000011\n1110000010010000\n0000000000000000\n1110001100001000\n"
+        );
+        assert_eq!(run_hack(&words, 6)[0], 5);
+    }
+
+    #[test]
+    fn labels_and_variables_get_addresses() {
+        let (words, symbols) = assemble("@i\nM=1\n(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n@j\nM=0\n").unwrap();
+        assert_eq!(words[0], 16);
+        assert_eq!(words[4], 2);
+        assert_eq!(words[6], 17);
+        assert_eq!(symbols.labels, [("LOOP".to_string(), 2)]);
+        assert_eq!(symbols.variables, [("i".to_string(), 16), ("j".to_string(), 17)]);
+    }
+
+    #[test]
+    fn accepts_any_order_of_destinations_and_commutative_operands() {
+        let (words, _) = assemble("MD=A+D\nDM=D+A\nAMD=M|D;JMP\n").unwrap();
+        assert_eq!(words[0], words[1]);
+        assert_eq!(words[2], assemble("AMD=D|M;JMP\n").unwrap().0[0]);
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let errors = assemble("@32768\nD=Q\n(LOOP)\n(LOOP)\nD;JXX\n").unwrap_err();
+        let found: Vec<(&str, usize)> = errors.iter().map(|error| (error.code, error.span.line)).collect();
+        assert_eq!(
+            found,
+            [(codes::INVALID_SYMBOL, 1), (codes::UNKNOWN_MNEMONIC, 2), (codes::DUPLICATE_LABEL, 4), (codes::UNKNOWN_MNEMONIC, 5)]
+        );
+    }
+
+    #[test]
+    fn reads_machine_code_back() {
+        let (words, _) = assemble(ADD).unwrap();
+        assert_eq!(parse_hack(&to_text(&words)).unwrap(), words);
+        assert_eq!(parse_hack("0101\n").unwrap_err().code, codes::INVALID_MACHINE_CODE);
+    }
+}
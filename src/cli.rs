@@ -0,0 +1,250 @@
+use jack_compiler::lint::{Level, LINTS};
+use jack_compiler::typeck::Leniency;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Tokenize,
+    Parse,
+    Check,
+    Compile,
+    Fmt,
+    Run,
+    Translate,
+    Assemble,
+    Disassemble,
+}
+
+/// An output `compile` can write for each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Tokens,
+    Xml,
+    Ast,
+    Vm,
+    Asm,
+    Hack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Rendered with source snippets, on stderr
+    Human,
+    /// One JSON object per line on stdout, ending in a summary record
+    Json,
+}
+
+/// The parsed command line.
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub path: PathBuf,
+    pub emit: Vec<Emit>,
+    pub out_dir: Option<PathBuf>,
+    pub leniency: Leniency,
+    pub color: ColorChoice,
+    pub message_format: MessageFormat,
+    /// Commands `run` may take before it gives up, unlimited if not given
+    pub max_steps: Option<u64>,
+    /// Whether to write symbol maps when assembling, or read them when disassembling
+    pub symbols: bool,
+    /// Where to write a SARIF log of the diagnostics
+    pub sarif: Option<PathBuf>,
+    /// Whether `translate` starts the program with the bootstrap code
+    pub bootstrap: bool,
+    /// `--allow`, `--warn` and `--deny` in the order given, applied over the project file
+    pub lint_levels: Vec<(String, Level)>,
+}
+
+pub const USAGE: &str = "Usage: jack-compiler <command> [options] <file_or_directory>";
+
+pub fn help() -> String {
+    let lints: Vec<&str> = LINTS.iter().map(|lint| lint.name).collect();
+    format!(
+        "\
+{USAGE}
+
+Commands:
+  tokenize    Print the tokens of each file
+  parse       Print the syntax tree of each file
+  check       Report errors and warnings without writing any output
+  compile     Compile each file to VM code
+  fmt         Print each file in the canonical layout
+  run         Compile the program and run it in the VM emulator
+  translate   Translate .vm files into one Hack assembly file for the whole program
+  assemble    Assemble each .asm file into Hack machine code
+  disassemble Print each .hack file as Hack assembly
+
+Options:
+  --emit=<kinds>           Outputs of `compile`, comma separated: tokens, xml, ast, vm, asm, hack [default: vm]
+                           asm and hack are one file for the whole program, with the bootstrap code
+                           and any other .vm files of the directory, such as the OS, linked in
+  --out-dir=<dir>          Write outputs into <dir> instead of next to the sources, or instead
+                           of printing them for `tokenize`, `parse`, `fmt` and `disassemble`
+  --max-steps=<n>          Stop `run` with an error after <n> VM commands [default: no limit]
+  --no-bootstrap           Leave the bootstrap code out of `translate`, for code that doesn't
+                           have a Sys.init, such as a single function to test
+  --symbols                Write a symbol map <name>.sym next to each .hack file, or with
+                           `disassemble`, name addresses from the .sym next to each .hack file
+  --strict                 Check types strictly instead of accepting common Jack idioms
+  --color=<when>           Color diagnostics: auto, always or never [default: auto]
+  --message-format=<fmt>   Diagnostics of `check`, `compile` and `assemble` as human or json [default: human]
+  --sarif=<file>           Also write the diagnostics of `check`, `compile` and `assemble` to <file> as a SARIF 2.1.0 log
+  --allow=<lint>           Don't report <lint>
+  --warn=<lint>            Report <lint> as a warning
+  --deny=<lint>            Report <lint> as an error
+  -h, --help               Print this help
+
+Lints: {}
+Lint levels can also be set in the [lints] table of a jack.toml next to the sources.
+
+Diagnostics and errors are written to stderr, or to stdout with --message-format=json.
+Exit status:
+  0  success, possibly with warnings
+  1  a file has compile errors
+  2  usage error, e.g. an unknown option or lint
+  3  I/O error, e.g. a file that can't be read or written
+  4  the program failed in `run`, or ran into --max-steps
+",
+        lints.join(", "),
+    )
+}
+
+/// Parses the arguments after the program name. `Ok(None)` means help was asked for.
+pub fn parse_args(args: &[String]) -> Result<Option<Cli>, String> {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(None);
+    }
+    let Some((command, rest)) = args.split_first() else {
+        return Err("missing command".to_string());
+    };
+    let command = match command.as_str() {
+        "tokenize" => Command::Tokenize,
+        "parse" => Command::Parse,
+        "check" => Command::Check,
+        "compile" => Command::Compile,
+        "fmt" => Command::Fmt,
+        "run" => Command::Run,
+        "translate" => Command::Translate,
+        "assemble" => Command::Assemble,
+        "disassemble" => Command::Disassemble,
+        other => return Err(format!("unknown command `{}`", other)),
+    };
+
+    let mut emit = None;
+    let mut out_dir = None;
+    let mut leniency = Leniency::Lenient;
+    let mut color = ColorChoice::Auto;
+    let mut message_format = None;
+    let mut sarif = None;
+    let mut symbols = false;
+    let mut bootstrap = true;
+    let mut max_steps = None;
+    let mut lint_levels = Vec::new();
+    let mut paths = Vec::new();
+    for arg in rest {
+        let (flag, value) = arg.split_once('=').unwrap_or((arg, ""));
+        match flag {
+            "--emit" => emit = Some(parse_emit(value)?),
+            "--out-dir" if value.is_empty() => return Err("--out-dir expects a directory".to_string()),
+            "--out-dir" => out_dir = Some(PathBuf::from(value)),
+            "--strict" if value.is_empty() => leniency = Leniency::Strict,
+            "--symbols" if value.is_empty() => symbols = true,
+            "--max-steps" => {
+                max_steps = Some(value.parse().map_err(|_| format!("invalid value `{}` for --max-steps, expected a number of steps", value))?)
+            }
+            "--no-bootstrap" if value.is_empty() => bootstrap = false,
+            "--color" => {
+                color = match value {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => return Err(format!("invalid value `{}` for --color, expected auto, always or never", value)),
+                }
+            }
+            "--message-format" => {
+                message_format = match value {
+                    "human" => Some(MessageFormat::Human),
+                    "json" => Some(MessageFormat::Json),
+                    _ => return Err(format!("invalid value `{}` for --message-format, expected human or json", value)),
+                }
+            }
+            "--sarif" if value.is_empty() => return Err("--sarif expects a file".to_string()),
+            "--sarif" => sarif = Some(PathBuf::from(value)),
+            "--allow" | "--warn" | "--deny" => {
+                let level = Level::parse(&flag["--".len()..]).expect("flag names match the level names");
+                lint_levels.push((value.to_string(), level));
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if emit.is_some() && command != Command::Compile {
+        return Err("--emit is only accepted by `compile`".to_string());
+    }
+    if !matches!(command, Command::Check | Command::Compile | Command::Assemble) {
+        if message_format.is_some() {
+            return Err("--message-format is only accepted by `check`, `compile` and `assemble`".to_string());
+        }
+        if sarif.is_some() {
+            return Err("--sarif is only accepted by `check`, `compile` and `assemble`".to_string());
+        }
+    }
+    if max_steps.is_some() && command != Command::Run {
+        return Err("--max-steps is only accepted by `run`".to_string());
+    }
+    if !bootstrap && command != Command::Translate {
+        return Err("--no-bootstrap is only accepted by `translate`".to_string());
+    }
+    if symbols && !matches!(command, Command::Compile | Command::Assemble | Command::Disassemble) {
+        return Err("--symbols is only accepted by `compile`, `assemble` and `disassemble`".to_string());
+    }
+    let path = match paths.len() {
+        0 => return Err("missing file or directory".to_string()),
+        1 => paths.remove(0),
+        _ => return Err("expected a single file or directory".to_string()),
+    };
+
+    Ok(Some(Cli {
+        command,
+        path,
+        emit: emit.unwrap_or_else(|| vec![Emit::Vm]),
+        out_dir,
+        leniency,
+        color,
+        message_format: message_format.unwrap_or(MessageFormat::Human),
+        max_steps,
+        symbols,
+        sarif,
+        bootstrap,
+        lint_levels,
+    }))
+}
+
+fn parse_emit(value: &str) -> Result<Vec<Emit>, String> {
+    let mut kinds = Vec::new();
+    for name in value.split(',') {
+        let kind = match name.trim() {
+            "tokens" => Emit::Tokens,
+            "xml" => Emit::Xml,
+            "ast" => Emit::Ast,
+            "vm" => Emit::Vm,
+            "asm" => Emit::Asm,
+            "hack" => Emit::Hack,
+            other => return Err(format!("unknown output `{}` for --emit, expected tokens, xml, ast, vm, asm or hack", other)),
+        };
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    Ok(kinds)
+}
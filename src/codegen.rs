@@ -0,0 +1,266 @@
+use crate::parser::{
+    ClassNode, ExpressionNode, StatementNode, SubroutineCallNode, SubroutineDecNode, SubroutineKind,
+    TermKind, TermNode, Type,
+};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::Span;
+use crate::symbol_table::{Symbol, SymbolTable, VarKind};
+use crate::tokenizer::{Keyword, MAX_INT_CONST};
+
+#[derive(Default)]
+pub struct CodeGenerator {
+    class_name: String,
+    symbols: SymbolTable,
+    label_counter: usize,
+    output: Vec<String>,
+}
+
+impl CodeGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lowers a whole class to VM code, one command per line.
+    pub fn compile_class(&mut self, class: &ClassNode) -> Result<String, Box<Diagnostic>> {
+        self.class_name = class.name.clone();
+        self.symbols = SymbolTable::for_class(class)?;
+        self.label_counter = 0;
+        self.output.clear();
+
+        for subroutine in &class.subroutine_decs {
+            self.compile_subroutine(subroutine)?;
+        }
+
+        let mut code = self.output.join("\n");
+        code.push('\n');
+        Ok(code)
+    }
+
+    fn compile_subroutine(&mut self, subroutine: &SubroutineDecNode) -> Result<(), Box<Diagnostic>> {
+        self.symbols.enter_subroutine(subroutine)?;
+
+        let local_count = self.symbols.var_count(VarKind::Local);
+        self.emit(format!("function {}.{} {}", self.class_name, subroutine.name, local_count));
+        match subroutine.kind {
+            SubroutineKind::Constructor => {
+                self.emit(format!("push constant {}", self.symbols.var_count(VarKind::Field)));
+                self.emit("call Memory.alloc 1");
+                self.emit("pop pointer 0");
+            }
+            SubroutineKind::Method => {
+                self.emit("push argument 0");
+                self.emit("pop pointer 0");
+            }
+            SubroutineKind::Function => {}
+        }
+
+        self.compile_statements(&subroutine.body.statements)
+    }
+
+    fn compile_statements(&mut self, statements: &[StatementNode]) -> Result<(), Box<Diagnostic>> {
+        for statement in statements {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, statement: &StatementNode) -> Result<(), Box<Diagnostic>> {
+        match statement {
+            StatementNode::Let(let_stmt) => {
+                let var = self.lookup(&let_stmt.var_name, let_stmt.span)?;
+                if let Some(index_expr) = &let_stmt.index_expr {
+                    // Compute the target address before the value, the value may use `that` too
+                    self.emit(format!("push {} {}", segment(var.kind), var.index));
+                    self.compile_expression(index_expr)?;
+                    self.emit("add");
+                    self.compile_expression(&let_stmt.value_expr)?;
+                    self.emit("pop temp 0");
+                    self.emit("pop pointer 1");
+                    self.emit("push temp 0");
+                    self.emit("pop that 0");
+                } else {
+                    self.compile_expression(&let_stmt.value_expr)?;
+                    self.emit(format!("pop {} {}", segment(var.kind), var.index));
+                }
+            }
+            StatementNode::If(if_stmt) => {
+                let id = self.next_label_id();
+                let else_label = format!("IF_ELSE{}", id);
+                let end_label = format!("IF_END{}", id);
+                self.compile_expression(&if_stmt.condition)?;
+                self.emit("not");
+                self.emit(format!("if-goto {}", else_label));
+                self.compile_statements(&if_stmt.if_block)?;
+                self.emit(format!("goto {}", end_label));
+                self.emit(format!("label {}", else_label));
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.compile_statements(else_block)?;
+                }
+                self.emit(format!("label {}", end_label));
+            }
+            StatementNode::While(while_stmt) => {
+                let id = self.next_label_id();
+                let start_label = format!("WHILE_EXP{}", id);
+                let end_label = format!("WHILE_END{}", id);
+                self.emit(format!("label {}", start_label));
+                self.compile_expression(&while_stmt.condition)?;
+                self.emit("not");
+                self.emit(format!("if-goto {}", end_label));
+                self.compile_statements(&while_stmt.body)?;
+                self.emit(format!("goto {}", start_label));
+                self.emit(format!("label {}", end_label));
+            }
+            StatementNode::Do(do_stmt) => {
+                self.compile_subroutine_call(&do_stmt.call)?;
+                // Discard the return value
+                self.emit("pop temp 0");
+            }
+            StatementNode::Return(return_stmt) => {
+                match &return_stmt.value {
+                    Some(expr) => self.compile_expression(expr)?,
+                    None => self.emit("push constant 0"),
+                }
+                self.emit("return");
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expr: &ExpressionNode) -> Result<(), Box<Diagnostic>> {
+        self.compile_term(&expr.initial_term)?;
+        for (op, term) in &expr.operations {
+            self.compile_term(term)?;
+            match op {
+                '+' => self.emit("add"),
+                '-' => self.emit("sub"),
+                '*' => self.emit("call Math.multiply 2"),
+                '/' => self.emit("call Math.divide 2"),
+                '&' => self.emit("and"),
+                '|' => self.emit("or"),
+                '<' => self.emit("lt"),
+                '>' => self.emit("gt"),
+                '=' => self.emit("eq"),
+                _ => unreachable!("the parser only produces known binary operators"),
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_term(&mut self, term: &TermNode) -> Result<(), Box<Diagnostic>> {
+        match &term.kind {
+            TermKind::IntConst(val) => self.emit(format!("push constant {}", val)),
+            TermKind::StrConst(s) => {
+                self.emit(format!("push constant {}", s.chars().count()));
+                self.emit("call String.new 1");
+                for c in s.chars() {
+                    self.emit(format!("push constant {}", c as u32));
+                    self.emit("call String.appendChar 2");
+                }
+            }
+            TermKind::KeywordConst(k) => match k {
+                Keyword::True => {
+                    self.emit("push constant 0");
+                    self.emit("not");
+                }
+                Keyword::False | Keyword::Null => self.emit("push constant 0"),
+                Keyword::This => self.emit("push pointer 0"),
+                _ => unreachable!("the parser only produces keyword constants"),
+            },
+            TermKind::VarName(name) => {
+                let var = self.lookup(name, term.span)?;
+                self.emit(format!("push {} {}", segment(var.kind), var.index));
+            }
+            TermKind::ArrayAccess(name, index_expr) => {
+                let var = self.lookup(name, term.span)?;
+                self.emit(format!("push {} {}", segment(var.kind), var.index));
+                self.compile_expression(index_expr)?;
+                self.emit("add");
+                self.emit("pop pointer 1");
+                self.emit("push that 0");
+            }
+            TermKind::SubroutineCall(call) => self.compile_subroutine_call(call)?,
+            TermKind::Parenthesized(expr) => self.compile_expression(expr)?,
+            // `push constant` only takes 0..32767, so -32768 is built as -32767 - 1
+            TermKind::UnaryOp('-', term) if term.kind == TermKind::IntConst(MAX_INT_CONST + 1) => {
+                self.emit(format!("push constant {}", MAX_INT_CONST));
+                self.emit("neg");
+                self.emit("push constant 1");
+                self.emit("sub");
+            }
+            TermKind::UnaryOp(op, term) => {
+                self.compile_term(term)?;
+                match op {
+                    '-' => self.emit("neg"),
+                    '~' => self.emit("not"),
+                    _ => unreachable!("the parser only produces known unary operators"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_subroutine_call(&mut self, call: &SubroutineCallNode) -> Result<(), Box<Diagnostic>> {
+        let mut arg_count = call.args.len();
+        let target = match &call.receiver {
+            // `foo(...)` is a method call on the current object
+            None => {
+                self.emit("push pointer 0");
+                arg_count += 1;
+                format!("{}.{}", self.class_name, call.name)
+            }
+            Some(receiver) => match self.try_lookup(receiver) {
+                // `var.foo(...)` is a method call on the object stored in `var`
+                Some(var) => {
+                    let class_name = match &var.var_type {
+                        Type::ClassName(class_name) => class_name.clone(),
+                        _ => return Err(Diagnostic::error(
+                            codes::INVALID_RECEIVER,
+                            format!("cannot call method `{}` on `{}`, which is not an object", call.name, receiver),
+                            call.span,
+                        ).with_label(var.span, format!("`{}` is declared here", receiver)).into()),
+                    };
+                    self.emit(format!("push {} {}", segment(var.kind), var.index));
+                    arg_count += 1;
+                    format!("{}.{}", class_name, call.name)
+                }
+                // `Class.foo(...)` is a function or constructor call
+                None => format!("{}.{}", receiver, call.name),
+            },
+        };
+
+        for arg in &call.args {
+            self.compile_expression(arg)?;
+        }
+        self.emit(format!("call {} {}", target, arg_count));
+        Ok(())
+    }
+
+    // Utility functions
+    fn try_lookup(&self, name: &str) -> Option<Symbol> {
+        self.symbols.lookup(name).cloned()
+    }
+
+    fn lookup(&self, name: &str, span: Span) -> Result<Symbol, Box<Diagnostic>> {
+        self.try_lookup(name).ok_or_else(|| {
+            Diagnostic::error(codes::UNDEFINED_VARIABLE, format!("cannot find variable `{}` in class `{}`", name, self.class_name), span).into()
+        })
+    }
+
+    fn next_label_id(&mut self) -> usize {
+        self.label_counter += 1;
+        self.label_counter - 1
+    }
+
+    fn emit(&mut self, command: impl Into<String>) {
+        self.output.push(command.into());
+    }
+}
+
+fn segment(kind: VarKind) -> &'static str {
+    match kind {
+        VarKind::Static => "static",
+        VarKind::Field => "this",
+        VarKind::Argument => "argument",
+        VarKind::Local => "local",
+    }
+}
@@ -0,0 +1,184 @@
+use crate::span::Span;
+use std::fmt;
+
+/// Stable error codes, grouped by the phase that reports them. Lints use
+/// their name as the code instead, see `lint`.
+pub mod codes {
+    // Lexical errors
+    pub const INVALID_CHARACTER: &str = "E0001";
+    pub const UNTERMINATED_STRING: &str = "E0002";
+    pub const INVALID_INTEGER: &str = "E0003";
+    pub const UNTERMINATED_COMMENT: &str = "E0004";
+    pub const INVALID_STRING_CHARACTER: &str = "E0005";
+
+    // Syntax errors
+    pub const UNEXPECTED_TOKEN: &str = "E0100";
+    pub const UNEXPECTED_EOF: &str = "E0101";
+
+    // Name resolution and code generation errors
+    pub const UNDEFINED_VARIABLE: &str = "E0200";
+    pub const DUPLICATE_VARIABLE: &str = "E0201";
+    pub const INVALID_RECEIVER: &str = "E0202";
+    pub const UNDEFINED_CLASS: &str = "E0203";
+    pub const UNDEFINED_SUBROUTINE: &str = "E0204";
+    pub const ARGUMENT_COUNT_MISMATCH: &str = "E0205";
+    pub const INVALID_CALL_KIND: &str = "E0206";
+    pub const THIS_OUTSIDE_METHOD: &str = "E0207";
+    pub const DUPLICATE_SUBROUTINE: &str = "E0208";
+
+    // Type errors
+    pub const TYPE_MISMATCH: &str = "E0300";
+    pub const INVALID_OPERAND: &str = "E0301";
+    pub const NON_BOOLEAN_CONDITION: &str = "E0302";
+    pub const VOID_VALUE: &str = "E0303";
+    pub const NOT_AN_ARRAY: &str = "E0304";
+
+    // Control flow errors
+    pub const MISSING_RETURN: &str = "E0400";
+    pub const RETURN_VALUE_IN_VOID: &str = "E0401";
+    pub const MISSING_RETURN_VALUE: &str = "E0402";
+    pub const CONSTRUCTOR_RETURN: &str = "E0403";
+
+    // VM code errors, from reading `.vm` files
+    pub const INVALID_VM_COMMAND: &str = "E0500";
+    pub const INVALID_VM_ARGUMENT: &str = "E0501";
+    pub const UNDEFINED_FUNCTION: &str = "E0502";
+
+    // Assembly and machine code errors
+    pub const UNKNOWN_MNEMONIC: &str = "E0600";
+    pub const INVALID_SYMBOL: &str = "E0601";
+    pub const DUPLICATE_LABEL: &str = "E0602";
+    pub const ROM_OVERFLOW: &str = "E0603";
+    pub const INVALID_MACHINE_CODE: &str = "E0604";
+
+    /// Every code with a short description of what it flags.
+    pub const DESCRIPTIONS: &[(&str, &str)] = &[
+        (INVALID_CHARACTER, "A character that cannot start any token"),
+        (UNTERMINATED_STRING, "A string constant without a closing quote on the same line"),
+        (INVALID_INTEGER, "An integer constant greater than 32767"),
+        (UNTERMINATED_COMMENT, "A `/*` comment without a closing `*/`"),
+        (INVALID_STRING_CHARACTER, "A character in a string constant whose code is greater than 32767"),
+        (UNEXPECTED_TOKEN, "A token the grammar doesn't allow at this point"),
+        (UNEXPECTED_EOF, "The file ends in the middle of a class"),
+        (UNDEFINED_VARIABLE, "A variable that is not declared"),
+        (DUPLICATE_VARIABLE, "A name declared twice in the same scope"),
+        (INVALID_RECEIVER, "A subroutine called on a value that is not an object"),
+        (UNDEFINED_CLASS, "A class that is neither in the program nor in the OS"),
+        (UNDEFINED_SUBROUTINE, "A subroutine the class doesn't declare"),
+        (ARGUMENT_COUNT_MISMATCH, "A call with the wrong number of arguments"),
+        (INVALID_CALL_KIND, "A method called like a function, or a function called on an object"),
+        (THIS_OUTSIDE_METHOD, "`this` or a field used in a function"),
+        (DUPLICATE_SUBROUTINE, "A subroutine declared twice in the same class"),
+        (TYPE_MISMATCH, "A value of the wrong type"),
+        (INVALID_OPERAND, "An operator applied to a value of the wrong type"),
+        (NON_BOOLEAN_CONDITION, "An `if` or `while` condition that is not a boolean"),
+        (VOID_VALUE, "The result of a void subroutine used as a value"),
+        (NOT_AN_ARRAY, "Indexing into a value that is not an Array"),
+        (MISSING_RETURN, "A subroutine that can reach its end without a `return`"),
+        (RETURN_VALUE_IN_VOID, "A void subroutine returning a value"),
+        (MISSING_RETURN_VALUE, "A `return` without a value in a non-void subroutine"),
+        (CONSTRUCTOR_RETURN, "A constructor that doesn't `return this`"),
+        (INVALID_VM_COMMAND, "A line of VM code that is not a command"),
+        (INVALID_VM_ARGUMENT, "A segment, index or name that a VM command doesn't accept"),
+        (UNDEFINED_FUNCTION, "A call to a function that none of the program's VM code defines"),
+        (UNKNOWN_MNEMONIC, "A destination, computation or jump the Hack CPU doesn't have"),
+        (INVALID_SYMBOL, "A malformed symbol, label or address"),
+        (DUPLICATE_LABEL, "A label declared twice, or with the name of a predefined symbol"),
+        (ROM_OVERFLOW, "A program with more instructions than the ROM holds"),
+        (INVALID_MACHINE_CODE, "A line of a .hack file that is not a 16-bit binary word, or a word that is not an instruction"),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A secondary location that explains the primary one, e.g. a previous declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    /// Suggested fixes, e.g. a similarly named variable.
+    pub helps: Vec<String>,
+    /// What the parser would have accepted instead, e.g. `';'` or `identifier`.
+    pub expected: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            helps: Vec::new(),
+            expected: Vec::new(),
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Warning, ..Diagnostic::error(code, message, span) }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.helps.push(help.into());
+        self
+    }
+
+    pub fn with_expected(mut self, expected: Vec<String>) -> Self {
+        self.expected = expected;
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {} (line {}, column {})", self.severity, self.code, self.message, self.span.line, self.span.column)?;
+        if self.expected.len() > 1 {
+            write!(f, "\n  expected one of: {}", self.expected.join(", "))?;
+        }
+        for label in &self.labels {
+            write!(f, "\n  line {}, column {}: {}", label.span.line, label.span.column, label.message)?;
+        }
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        for help in &self.helps {
+            write!(f, "\n  help: {}", help)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use crate::assembler::{SymbolMap, COMPUTATIONS, DESTINATIONS, JUMPS, PREDEFINED};
+use std::collections::{BTreeMap, HashMap};
+
+// Machine code back to Hack assembly that assembles to the same words:
+//
+// (L4)
+//     @SP                 // 2
+//     AM=M-1              // 3
+//     @L4                 // 4
+//     D;JNE               // 5
+//
+// An A-instruction followed by a jump names a ROM address, which gets a label,
+// and one followed by an instruction that uses M names a RAM address, which is
+// written as the predefined symbol for it if there is one. Label names come
+// from the symbol map when one is given, otherwise they are made up from the
+// address. Variables from the symbol map are noted in comments, as writing
+// them as symbols could give them other addresses when reassembled.
+//
+// Words that no instruction assembles to, such as a C-instruction with ALU
+// bits that are no computation, fail the disassembly instead of losing bits.
+
+const COMMENT_COLUMN: usize = 24;
+
+enum Decoded {
+    Address(u16),
+    Compute { dest: &'static str, comp: String, jump: &'static str },
+}
+
+impl Decoded {
+    fn jumps(&self) -> bool {
+        matches!(self, Decoded::Compute { jump, .. } if !jump.is_empty())
+    }
+
+    fn uses_memory(&self) -> bool {
+        matches!(self, Decoded::Compute { dest, comp, .. } if dest.contains('M') || comp.contains('M'))
+    }
+}
+
+/// Disassembles `words`, naming labels and variables from `symbols` if given,
+/// or returns the index of every word no instruction assembles to, with why.
+pub fn disassemble(words: &[u16], symbols: Option<&SymbolMap>) -> Result<String, Vec<(usize, String)>> {
+    let mut decoded = Vec::new();
+    let mut invalid = Vec::new();
+    for (i, &word) in words.iter().enumerate() {
+        match decode(word) {
+            Ok(instruction) => decoded.push(instruction),
+            Err(reason) => invalid.push((i, reason)),
+        }
+    }
+    if !invalid.is_empty() {
+        return Err(invalid);
+    }
+
+    let mut labels: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+    if let Some(symbols) = symbols {
+        for (name, address) in symbols.labels.iter().filter(|(_, address)| *address as usize <= words.len()) {
+            labels.entry(*address).or_default().push(name.clone());
+        }
+    }
+    for (instruction, next) in decoded.iter().zip(decoded.iter().skip(1)) {
+        // A jump out of the program keeps its address, a label there would move when reassembled
+        if let Decoded::Address(address) = instruction
+            && next.jumps()
+            && *address as usize <= words.len()
+        {
+            labels.entry(*address).or_insert_with(|| vec![format!("L{}", address)]);
+        }
+    }
+    let variables: HashMap<u16, &str> =
+        symbols.map(|symbols| symbols.variables.iter().map(|(name, address)| (*address, name.as_str())).collect()).unwrap_or_default();
+
+    let mut out = String::new();
+    for (address, instruction) in decoded.iter().enumerate() {
+        for name in labels.get(&(address as u16)).into_iter().flatten() {
+            out.push_str(&format!("({})\n", name));
+        }
+        let next = decoded.get(address + 1);
+        let (text, variable) = match instruction {
+            Decoded::Address(value) if next.is_some_and(Decoded::jumps) && labels.contains_key(value) => (format!("@{}", labels[value][0]), None),
+            Decoded::Address(value) if next.is_some_and(Decoded::uses_memory) => match PREDEFINED.iter().find(|(_, predefined)| predefined == value) {
+                Some((name, _)) => (format!("@{}", name), None),
+                None => (format!("@{}", value), variables.get(value)),
+            },
+            Decoded::Address(value) => (format!("@{}", value), None),
+            Decoded::Compute { dest, comp, jump } => {
+                let mut text = String::new();
+                if !dest.is_empty() {
+                    text.push_str(&format!("{}=", dest));
+                }
+                text.push_str(comp);
+                if !jump.is_empty() {
+                    text.push_str(&format!(";{}", jump));
+                }
+                (text, None)
+            }
+        };
+        let comment = match variable {
+            Some(name) => format!("// {}, variable {}", address, name),
+            None => format!("// {}", address),
+        };
+        let line = format!("    {}", text);
+        out.push_str(&format!("{:width$}{}\n", line, comment, width = COMMENT_COLUMN));
+    }
+    // Labels past the last instruction, e.g. the end of a program that falls through
+    for name in labels.range(words.len() as u16..).flat_map(|(_, names)| names) {
+        out.push_str(&format!("({})\n", name));
+    }
+    Ok(out)
+}
+
+fn decode(word: u16) -> Result<Decoded, String> {
+    if word & 0x8000 == 0 {
+        return Ok(Decoded::Address(word));
+    }
+    // Bits 13 and 14 are unused and always set by the assembler
+    if word >> 13 != 0b111 {
+        return Err(format!("`{:016b}` is not an instruction, bits 13 and 14 of a C-instruction are always set", word));
+    }
+    let alu_bits = (word >> 6) & 0b111111;
+    let Some((comp, _)) = COMPUTATIONS.iter().find(|(_, bits)| *bits == alu_bits) else {
+        return Err(format!("`{:016b}` is not an instruction, its ALU bits are not a computation", word));
+    };
+    let comp = match word & 0x1000 {
+        0 => comp.to_string(),
+        _ if comp.contains('A') => comp.replace('A', "M"),
+        _ => return Err(format!("`{:016b}` sets the a bit, which the computation `{}` doesn't read", word, comp)),
+    };
+    Ok(Decoded::Compute { dest: DESTINATIONS[((word >> 3) & 0b111) as usize], comp, jump: JUMPS[(word & 0b111) as usize] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::translate::translate_program;
+    use crate::vm::VmFile;
+
+    const SYS: &str = "\
+function Sys.init 0
+call Main.main 0
+pop temp 0
+label END
+goto END
+";
+
+    const MAIN: &str = "\
+function Main.main 2
+push constant 0
+pop local 0
+label WHILE_EXP0
+push local 0
+push constant 10
+lt
+not
+if-goto WHILE_END0
+push local 1
+push local 0
+call Main.double 1
+add
+pop local 1
+push local 0
+push constant 1
+add
+pop local 0
+goto WHILE_EXP0
+label WHILE_END0
+push constant 0
+return
+function Main.double 0
+push argument 0
+push argument 0
+add
+return
+";
+
+    fn round_trip(words: &[u16]) -> Vec<u16> {
+        let asm = disassemble(words, None).unwrap();
+        assemble(&asm).unwrap().0
+    }
+
+    #[test]
+    fn translated_program_round_trips() {
+        let files = [VmFile::parse("Sys", SYS).unwrap(), VmFile::parse("Main", MAIN).unwrap()];
+        let (words, _) = assemble(&translate_program(&files, true).unwrap()).unwrap();
+        assert_eq!(round_trip(&words), words);
+    }
+
+    #[test]
+    fn names_addresses_from_the_symbol_map() {
+        let (words, symbols) = assemble("@i\nM=0\n(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n").unwrap();
+        let asm = disassemble(&words, Some(&symbols)).unwrap();
+        assert!(asm.contains("(LOOP)\n"));
+        assert!(asm.contains("@LOOP"));
+        assert!(asm.contains("variable i"));
+        assert_eq!(assemble(&asm).unwrap().0, words);
+    }
+
+    #[test]
+    fn every_computation_round_trips() {
+        let words: Vec<u16> = (0x8000..=u16::MAX).filter(|word| disassemble(&[*word], None).is_ok()).collect();
+        assert_eq!(round_trip(&words), words);
+    }
+
+    #[test]
+    fn refuses_words_no_instruction_assembles_to() {
+        // No ALU computation, unused bits cleared, and the a bit with a computation that doesn't read M
+        let words = [0b1110_0000_0100_0000, 0b1000_1100_0001_0000, 0b1111_1111_1100_0000, 0b0000_0000_0000_0001];
+        let invalid: Vec<usize> = disassemble(&words, None).unwrap_err().into_iter().map(|(i, _)| i).collect();
+        assert_eq!(invalid, [0, 1, 2]);
+    }
+}
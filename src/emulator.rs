@@ -0,0 +1,467 @@
+use crate::os::{self, Effect, Native, Os};
+use crate::vm::{ArithmeticOp, Segment, VmCommand, VmFile};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// Runs VM code directly, with the memory layout the VM translator gives it on
+// the Hack computer, so that RAM looks the same afterwards:
+//
+// RAM[0..5]        SP, LCL, ARG, THIS and THAT
+// RAM[5..13]       the temp segment
+// RAM[16..256]     static variables, each file's after the previous file's
+// RAM[256..2048]   the stack, with a frame of five words per call
+// RAM[2048..16384] the heap
+// RAM[16384..]     the screen and the keyboard
+//
+// Return addresses are indexes of commands rather than ROM addresses. Calls to
+// OS functions that no file defines go to the native OS in `os`, so a program
+// runs without the OS's `.vm` files.
+
+pub const RAM_SIZE: usize = 32768;
+
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+const TEMP: usize = 5;
+const STATIC: usize = 16;
+const STACK: usize = 256;
+const HEAP: usize = 2048;
+
+/// Callers a runtime error names before it counts the rest.
+const TRACE_CALLERS: usize = 8;
+
+/// Why `run` returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The program returned from the function it was started with, called
+    /// `Sys.halt`, or is stuck in a `goto` to itself, the usual way of ending
+    /// a Hack program.
+    Halted,
+    /// The step limit was reached first
+    StepLimit,
+}
+
+// A command with its labels and statics resolved
+#[derive(Debug, Clone)]
+enum Op {
+    Arithmetic(ArithmeticOp),
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    /// A static variable, by RAM address
+    PushStatic(usize),
+    PopStatic(usize),
+    Goto(usize),
+    IfGoto(usize),
+    Function { locals: u16 },
+    Call { callee: Callee, args: u16 },
+    Return,
+}
+
+#[derive(Debug, Clone)]
+enum Callee {
+    /// Index into `functions`
+    Function(usize),
+    /// An OS function with no definition in the loaded files
+    Native(&'static Native),
+    /// Fails when it is reached, so that a program can run as long as it doesn't call it
+    Undefined(String),
+}
+
+#[derive(Debug)]
+struct Frame {
+    /// Index into `functions`
+    function: usize,
+    /// Where the caller continues, `None` for the function the run started with
+    return_to: Option<usize>,
+}
+
+/// An emulator loaded with a program, e.g. to run the `.vm` files of a
+/// compiled Jack program and inspect RAM afterwards:
+///
+/// ```text
+/// let mut emulator = Emulator::new(&vm_files)?;
+/// emulator.start("Sys.init")?;
+/// emulator.run(Some(1_000_000))?;
+/// assert_eq!(emulator.ram()[8000], 80);
+/// ```
+#[derive(Debug)]
+pub struct Emulator {
+    ops: Vec<Op>,
+    /// Function names and the index of their `function` command
+    functions: Vec<(String, usize)>,
+    ram: Vec<i16>,
+    pc: usize,
+    frames: Vec<Frame>,
+    steps: u64,
+    os: Os,
+}
+
+impl Emulator {
+    /// Loads `files`, resolving labels within each function, calls between
+    /// functions and each file's statics.
+    pub fn new(files: &[VmFile]) -> Result<Emulator, String> {
+        let mut ops = Vec::new();
+        let mut functions: Vec<(String, usize)> = Vec::new();
+        // (index of the op, scope, label) of jumps, resolved once every label has been seen
+        let mut jumps = Vec::new();
+        let mut labels = HashMap::new();
+        let mut calls = Vec::new();
+        let mut next_static = STATIC;
+
+        for file in files {
+            let mut scope = file.name.clone();
+            let statics = file
+                .commands
+                .iter()
+                .filter_map(|command| match command {
+                    VmCommand::Push(Segment::Static, index) | VmCommand::Pop(Segment::Static, index) => Some(*index as usize + 1),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0);
+            if next_static + statics > STACK {
+                return Err(format!("the static variables of {}.vm don't fit below the stack at RAM[{}]", file.name, STACK));
+            }
+
+            for command in &file.commands {
+                let op = match command {
+                    VmCommand::Arithmetic(op) => Op::Arithmetic(*op),
+                    VmCommand::Push(Segment::Static, index) => Op::PushStatic(next_static + *index as usize),
+                    VmCommand::Pop(Segment::Static, index) => Op::PopStatic(next_static + *index as usize),
+                    VmCommand::Push(segment, index) => Op::Push(*segment, *index),
+                    VmCommand::Pop(segment, index) => Op::Pop(*segment, *index),
+                    VmCommand::Label(label) => {
+                        if labels.insert((scope.clone(), label.clone()), ops.len()).is_some() {
+                            return Err(format!("label `{}` is declared twice in `{}`", label, scope));
+                        }
+                        continue;
+                    }
+                    VmCommand::Goto(label) | VmCommand::IfGoto(label) => {
+                        jumps.push((ops.len(), scope.clone(), label.clone()));
+                        if matches!(command, VmCommand::Goto(_)) { Op::Goto(0) } else { Op::IfGoto(0) }
+                    }
+                    VmCommand::Function(name, locals) => {
+                        if functions.iter().any(|(defined, _)| defined == name) {
+                            return Err(format!("function `{}` is defined twice", name));
+                        }
+                        scope = name.clone();
+                        functions.push((name.clone(), ops.len()));
+                        Op::Function { locals: *locals }
+                    }
+                    VmCommand::Call(name, args) => {
+                        calls.push((ops.len(), name.clone()));
+                        Op::Call { callee: Callee::Undefined(name.clone()), args: *args }
+                    }
+                    VmCommand::Return => Op::Return,
+                };
+                ops.push(op);
+            }
+            next_static += statics;
+        }
+
+        for (index, scope, label) in jumps {
+            let target = *labels.get(&(scope.clone(), label.clone())).ok_or_else(|| format!("label `{}` is not declared in `{}`", label, scope))?;
+            match &mut ops[index] {
+                Op::Goto(to) | Op::IfGoto(to) => *to = target,
+                _ => unreachable!("jumps are recorded for gotos"),
+            }
+        }
+        for (index, name) in calls {
+            let resolved = match functions.iter().position(|(defined, _)| *defined == name) {
+                Some(function) => Callee::Function(function),
+                None => match os::lookup(&name) {
+                    Some(native) => Callee::Native(native),
+                    None => continue,
+                },
+            };
+            if let Op::Call { callee, .. } = &mut ops[index] {
+                *callee = resolved;
+            }
+        }
+
+        let mut ram = vec![0; RAM_SIZE];
+        os::init_heap(&mut ram);
+        Ok(Emulator { ops, functions, ram, pc: 0, frames: Vec::new(), steps: 0, os: Os::default() })
+    }
+
+    /// Where the OS reads the keyboard from and writes its output to, nowhere
+    /// until this is called.
+    pub fn set_console(&mut self, input: Box<dyn BufRead>, output: Box<dyn Write>) {
+        self.os.set_console(input, output);
+    }
+
+    /// Sets up the stack the way the bootstrap code does and calls `function`
+    /// with no arguments. Anything left from an earlier run stays in RAM.
+    /// Without a definition of its own, `Sys.init` runs `Main.main`.
+    pub fn start(&mut self, function: &str) -> Result<(), String> {
+        let index = match self.functions.iter().position(|(name, _)| name == function) {
+            Some(index) => index,
+            None if function == "Sys.init" => return self.start("Main.main"),
+            None => return Err(format!("function `{}` is not defined", function)),
+        };
+        self.ram[SP] = STACK as i16;
+        self.frames.clear();
+        self.frames.push(Frame { function: index, return_to: None });
+        // Like a call: no arguments, then a frame of five words with nowhere to return to
+        self.ram[ARG] = STACK as i16;
+        self.ram[SP] += 5;
+        self.ram[LCL] = self.ram[SP];
+        self.pc = self.functions[index].1;
+        Ok(())
+    }
+
+    /// Runs until the program halts, or for at most `step_limit` commands.
+    pub fn run(&mut self, step_limit: Option<u64>) -> Result<Stop, String> {
+        let mut remaining = step_limit;
+        while !self.halted() {
+            if remaining == Some(0) {
+                return Ok(Stop::StepLimit);
+            }
+            self.step()?;
+            remaining = remaining.map(|steps| steps - 1);
+        }
+        Ok(Stop::Halted)
+    }
+
+    /// Whether there is nothing left to run, or only a `goto` to itself.
+    pub fn halted(&self) -> bool {
+        self.frames.is_empty() || matches!(self.ops.get(self.pc), Some(Op::Goto(to)) if *to == self.pc)
+    }
+
+    /// Runs a single command.
+    pub fn step(&mut self) -> Result<(), String> {
+        if self.frames.is_empty() {
+            return Err("the program is not running".to_string());
+        }
+        let Some(op) = self.ops.get(self.pc).cloned() else {
+            return Err(self.error("ran past the last command"));
+        };
+        self.steps += 1;
+        self.pc += 1;
+        match op {
+            Op::Arithmetic(op) => self.arithmetic(op)?,
+            Op::Push(segment, index) => {
+                let value = match segment {
+                    Segment::Constant => index as i16,
+                    _ => self.read(self.segment_address(segment, index)?)?,
+                };
+                self.push(value)?;
+            }
+            Op::Pop(segment, index) => {
+                let value = self.pop()?;
+                let address = self.segment_address(segment, index)?;
+                self.write(address, value)?;
+            }
+            Op::PushStatic(address) => self.push(self.ram[address])?,
+            Op::PopStatic(address) => self.ram[address] = self.pop()?,
+            Op::Goto(to) => self.pc = to,
+            Op::IfGoto(to) => {
+                if self.pop()? != 0 {
+                    self.pc = to;
+                }
+            }
+            Op::Function { locals } => {
+                for _ in 0..locals {
+                    self.push(0)?;
+                }
+            }
+            Op::Call { callee, args } => self.call(&callee, args)?,
+            Op::Return => self.return_()?,
+        }
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, op: ArithmeticOp) -> Result<(), String> {
+        let y = self.pop()?;
+        let result = match op {
+            ArithmeticOp::Neg => y.wrapping_neg(),
+            ArithmeticOp::Not => !y,
+            _ => {
+                let x = self.pop()?;
+                match op {
+                    ArithmeticOp::Add => x.wrapping_add(y),
+                    ArithmeticOp::Sub => x.wrapping_sub(y),
+                    ArithmeticOp::And => x & y,
+                    ArithmeticOp::Or => x | y,
+                    ArithmeticOp::Eq => -((x == y) as i16),
+                    ArithmeticOp::Gt => -((x > y) as i16),
+                    ArithmeticOp::Lt => -((x < y) as i16),
+                    ArithmeticOp::Neg | ArithmeticOp::Not => unreachable!("unary operators are handled above"),
+                }
+            }
+        };
+        self.push(result)
+    }
+
+    fn call(&mut self, callee: &Callee, args: u16) -> Result<(), String> {
+        let function = match callee {
+            Callee::Function(function) => *function,
+            Callee::Native(native) => return self.call_native(native, args),
+            Callee::Undefined(name) => return Err(self.error(&format!("called the undefined function `{}`", name))),
+        };
+        let sp = self.ram[SP];
+        self.push(self.pc as i16)?;
+        for pointer in [LCL, ARG, THIS, THAT] {
+            self.push(self.ram[pointer])?;
+        }
+        self.ram[ARG] = sp.wrapping_sub(args as i16);
+        self.ram[LCL] = self.ram[SP];
+        self.frames.push(Frame { function, return_to: Some(self.pc) });
+        self.pc = self.functions[function].1;
+        Ok(())
+    }
+
+    // Runs the function on the arguments at the top of the stack, which its result replaces
+    fn call_native(&mut self, native: &Native, args: u16) -> Result<(), String> {
+        if args != native.args {
+            return Err(self.error(&format!("`{}` takes {} arguments, called with {}", native.name, native.args, args)));
+        }
+        let sp = self.stack_pointer()?;
+        if sp < STACK + args as usize {
+            return Err(self.error("popped from an empty stack"));
+        }
+        let values = self.ram[sp - args as usize..sp].to_vec();
+        let effect = (native.run)(&mut self.os, &mut self.ram, &values).map_err(|message| self.trace(&message, Some(native.name)))?;
+        match effect {
+            Effect::Return(value) => {
+                self.ram[SP] -= args as i16;
+                self.push(value)
+            }
+            Effect::Halt => {
+                self.frames.clear();
+                Ok(())
+            }
+            Effect::RunMain => self.start("Main.main"),
+        }
+    }
+
+    fn return_(&mut self) -> Result<(), String> {
+        let frame = self.ram[LCL] as usize;
+        let value = self.pop()?;
+        let arg = self.ram[ARG] as usize;
+        self.write(arg, value)?;
+        self.ram[SP] = (arg + 1) as i16;
+        for (offset, pointer) in [THAT, THIS, ARG, LCL].into_iter().enumerate() {
+            self.ram[pointer] = self.read(frame.wrapping_sub(offset + 1))?;
+        }
+        let frame = self.frames.pop().expect("a function is running");
+        if let Some(return_to) = frame.return_to {
+            self.pc = return_to;
+        }
+        Ok(())
+    }
+
+    fn segment_address(&self, segment: Segment, index: u16) -> Result<usize, String> {
+        let index = index as usize;
+        let base = match segment {
+            Segment::Local => self.ram[LCL],
+            Segment::Argument => self.ram[ARG],
+            Segment::This => self.ram[THIS],
+            Segment::That => self.ram[THAT],
+            Segment::Pointer => return Ok(THIS + index),
+            Segment::Temp => return Ok(TEMP + index),
+            Segment::Static | Segment::Constant => unreachable!("resolved when loading"),
+        };
+        Ok((base as u16 as usize).wrapping_add(index))
+    }
+
+    fn push(&mut self, value: i16) -> Result<(), String> {
+        let sp = self.stack_pointer()?;
+        if sp == HEAP {
+            return Err(self.error("stack overflow"));
+        }
+        self.ram[sp] = value;
+        self.ram[SP] += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i16, String> {
+        let sp = self.stack_pointer()?;
+        if sp == STACK {
+            return Err(self.error("popped from an empty stack"));
+        }
+        self.ram[SP] -= 1;
+        Ok(self.ram[sp - 1])
+    }
+
+    // SP, which the program can overwrite like any other RAM word, as long as
+    // it still points into the stack
+    fn stack_pointer(&self) -> Result<usize, String> {
+        let sp = self.ram[SP] as u16 as usize;
+        if !(STACK..=HEAP).contains(&sp) {
+            return Err(self.error(&format!("the stack pointer was set to {}, outside the stack", self.ram[SP])));
+        }
+        Ok(sp)
+    }
+
+    fn read(&self, address: usize) -> Result<i16, String> {
+        self.ram.get(address).copied().ok_or_else(|| self.error(&format!("read from RAM[{}], which doesn't exist", address)))
+    }
+
+    fn write(&mut self, address: usize, value: i16) -> Result<(), String> {
+        match self.ram.get_mut(address) {
+            Some(word) => {
+                *word = value;
+                Ok(())
+            }
+            None => Err(self.error(&format!("wrote to RAM[{}], which doesn't exist", address))),
+        }
+    }
+
+    // Says where the program was, e.g. "stack overflow in `Main.fib`, called from `Main.main`"
+    fn error(&self, message: &str) -> String {
+        self.trace(message, None)
+    }
+
+    // Like `error`, for a native function called from the running one. Runs of
+    // the same function, from recursion, are named once with their count, and
+    // only the innermost callers are named.
+    fn trace(&self, message: &str, native: Option<&str>) -> String {
+        let running = self.frames.iter().rev().map(|frame| self.functions[frame.function].0.as_str());
+        let mut runs: Vec<(&str, usize)> = Vec::new();
+        for name in native.into_iter().chain(running) {
+            match runs.last_mut() {
+                Some((last, count)) if *last == name => *count += 1,
+                _ => runs.push((name, 1)),
+            }
+        }
+        let mut names = runs.iter().map(|&(name, count)| match count {
+            1 => format!("`{}`", name),
+            _ => format!("`{}` ({} frames)", name, count),
+        });
+        let Some(innermost) = names.next() else {
+            return message.to_string();
+        };
+        let mut callers: Vec<String> = names.take(TRACE_CALLERS).collect();
+        let more: usize = runs.iter().skip(1 + callers.len()).map(|(_, count)| count).sum();
+        if more > 0 {
+            callers.push(format!("and {} more frame{}", more, if more == 1 { "" } else { "s" }));
+        }
+        if callers.is_empty() {
+            format!("{} in {}", message, innermost)
+        } else {
+            format!("{} in {}, called from {}", message, innermost, callers.join(", "))
+        }
+    }
+
+    pub fn ram(&self) -> &[i16] {
+        &self.ram
+    }
+
+    /// RAM to set up input before a run, e.g. the keyboard register.
+    pub fn ram_mut(&mut self) -> &mut [i16] {
+        &mut self.ram
+    }
+
+    /// Commands run so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The functions being run, innermost last.
+    pub fn call_stack(&self) -> Vec<&str> {
+        self.frames.iter().map(|frame| self.functions[frame.function].0.as_str()).collect()
+    }
+}
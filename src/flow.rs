@@ -0,0 +1,90 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::parser::{ClassNode, ExpressionNode, StatementNode, SubroutineDecNode, SubroutineKind, TermKind};
+use crate::tokenizer::Keyword;
+
+/// Checks how the subroutines of `class` return: every path has to end in a
+/// `return` because the VM has no implicit one, the value has to match the
+/// declared return type being `void` or not, and constructors have to
+/// `return this`.
+pub fn check_class(class: &ClassNode) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    for subroutine in &class.subroutine_decs {
+        check_returns(subroutine, &subroutine.body.statements, &mut errors);
+
+        if !always_returns(&subroutine.body.statements) {
+            let error = match &subroutine.return_type {
+                Some(return_type) => Diagnostic::error(
+                    codes::MISSING_RETURN,
+                    format!("`{}` must return a value of type `{}` on every path", subroutine.name, return_type),
+                    subroutine.span,
+                ),
+                None => Diagnostic::error(
+                    codes::MISSING_RETURN,
+                    format!("`{}` can reach its end without a `return`", subroutine.name),
+                    subroutine.span,
+                )
+                .with_note("Jack has no implicit return, every subroutine has to end in `return`")
+                .with_help("add `return;` at the end of the subroutine"),
+            };
+            errors.push(error);
+        }
+    }
+    errors
+}
+
+// Checks each `return` against the kind and return type of `subroutine`
+fn check_returns(subroutine: &SubroutineDecNode, statements: &[StatementNode], errors: &mut Vec<Diagnostic>) {
+    for statement in statements {
+        match statement {
+            StatementNode::Return(return_stmt) => {
+                let error = match (&subroutine.kind, &subroutine.return_type, &return_stmt.value) {
+                    (SubroutineKind::Constructor, _, value) if !value.as_deref().is_some_and(is_this) => Some(
+                        Diagnostic::error(codes::CONSTRUCTOR_RETURN, format!("constructor `{}` must return `this`", subroutine.name), return_stmt.span)
+                            .with_help("write `return this;`"),
+                    ),
+                    (_, None, Some(value)) => Some(
+                        Diagnostic::error(codes::RETURN_VALUE_IN_VOID, format!("`{}` is void and cannot return a value", subroutine.name), value.span)
+                            .with_help("write `return;`"),
+                    ),
+                    (_, Some(return_type), None) => Some(Diagnostic::error(
+                        codes::MISSING_RETURN_VALUE,
+                        format!("`{}` must return a value of type `{}`", subroutine.name, return_type),
+                        return_stmt.span,
+                    )),
+                    _ => None,
+                };
+                errors.extend(error);
+            }
+            StatementNode::If(if_stmt) => {
+                check_returns(subroutine, &if_stmt.if_block, errors);
+                if let Some(else_block) = &if_stmt.else_block {
+                    check_returns(subroutine, else_block, errors);
+                }
+            }
+            StatementNode::While(while_stmt) => check_returns(subroutine, &while_stmt.body, errors),
+            StatementNode::Let(_) | StatementNode::Do(_) => {}
+        }
+    }
+}
+
+/// Whether control can never run past the end of `statements`. A `while (true)`
+/// counts, as Jack has no `break` and such a loop can only be left by returning.
+pub fn always_returns(statements: &[StatementNode]) -> bool {
+    statements.iter().any(|statement| match statement {
+        StatementNode::Return(_) => true,
+        StatementNode::If(if_stmt) => match &if_stmt.else_block {
+            Some(else_block) => always_returns(&if_stmt.if_block) && always_returns(else_block),
+            None => false,
+        },
+        StatementNode::While(while_stmt) => is_keyword(&while_stmt.condition, Keyword::True),
+        StatementNode::Let(_) | StatementNode::Do(_) => false,
+    })
+}
+
+fn is_this(expr: &ExpressionNode) -> bool {
+    is_keyword(expr, Keyword::This)
+}
+
+fn is_keyword(expr: &ExpressionNode, keyword: Keyword) -> bool {
+    expr.operations.is_empty() && expr.initial_term.kind == TermKind::KeywordConst(keyword)
+}
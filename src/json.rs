@@ -0,0 +1,123 @@
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
+use std::fmt;
+use std::path::Path;
+
+// Machine readable diagnostics for `--message-format=json`: one object per
+// line, each with a `type` field, ending in a single `summary` record.
+//
+// {"type":"diagnostic","file":"Main.jack","severity":"error","code":"E0100",...}
+// {"type":"summary","files":2,"succeeded":1,"failed":1,"errors":1,"warnings":0,"success":false}
+
+/// A JSON value, written without any whitespace so that records fit on one line.
+pub enum Json {
+    Bool(bool),
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys stay in the order given
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    pub fn string(value: impl Into<String>) -> Json {
+        Json::String(value.into())
+    }
+
+    pub fn strings(values: &[String]) -> Json {
+        Json::Array(values.iter().map(|value| Json::string(value.as_str())).collect())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// The `diagnostic` record for a diagnostic reported in `path`. Helps are
+/// listed as `suggestions`.
+pub fn diagnostic_record(diagnostic: &Diagnostic, path: &Path) -> Json {
+    let labels = diagnostic
+        .labels
+        .iter()
+        .map(|label| Json::Object(vec![("span", span(label.span)), ("message", Json::string(label.message.as_str()))]))
+        .collect();
+    Json::Object(vec![
+        ("type", Json::string("diagnostic")),
+        ("file", Json::string(path.display().to_string())),
+        ("severity", Json::string(diagnostic.severity.to_string())),
+        ("code", Json::string(diagnostic.code)),
+        ("message", Json::string(diagnostic.message.as_str())),
+        ("span", span(diagnostic.span)),
+        ("labels", Json::Array(labels)),
+        ("notes", Json::strings(&diagnostic.notes)),
+        ("suggestions", Json::strings(&diagnostic.helps)),
+        ("expected", Json::strings(&diagnostic.expected)),
+    ])
+}
+
+/// The record that ends the output, with totals over all files.
+pub fn summary_record(files: usize, failed: usize, errors: usize, warnings: usize, success: bool) -> Json {
+    Json::Object(vec![
+        ("type", Json::string("summary")),
+        ("files", Json::Number(files)),
+        ("succeeded", Json::Number(files - failed)),
+        ("failed", Json::Number(failed)),
+        ("errors", Json::Number(errors)),
+        ("warnings", Json::Number(warnings)),
+        ("success", Json::Bool(success)),
+    ])
+}
+
+fn span(span: Span) -> Json {
+    Json::Object(vec![
+        ("start", Json::Number(span.start)),
+        ("end", Json::Number(span.end)),
+        ("line", Json::Number(span.line)),
+        ("column", Json::Number(span.column)),
+        ("end_line", Json::Number(span.end_line)),
+        ("end_column", Json::Number(span.end_column)),
+    ])
+}
@@ -0,0 +1,81 @@
+//! A compiler for the Jack language of nand2tetris, along with the rest of
+//! the toolchain below it: a VM translator, an assembler, a disassembler and
+//! a VM emulator. The `jack-compiler` binary is a command line over this.
+
+pub mod span;
+pub mod diagnostic;
+pub mod tokenizer;
+pub mod parser;
+pub mod symbol_table;
+pub mod codegen;
+pub mod xml;
+pub mod report;
+pub mod signatures;
+pub mod resolve;
+pub mod typeck;
+pub mod flow;
+pub mod lint;
+pub mod pretty;
+pub mod json;
+pub mod sarif;
+pub mod vm;
+pub mod translate;
+pub mod assembler;
+pub mod disassembler;
+pub mod emulator;
+mod os;
+
+use codegen::CodeGenerator;
+use diagnostic::{Diagnostic, Severity};
+use lint::LintConfig;
+use parser::{ClassNode, Parser};
+use signatures::ProgramSignatures;
+use typeck::Leniency;
+use vm::VmFile;
+
+/// Resolves names, checks types and returns and runs the lints over a class
+/// that parsed without errors, read from `file_stem`.jack.
+pub fn check_class(class: &ClassNode, file_stem: &str, signatures: &ProgramSignatures, leniency: Leniency, lints: &LintConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = resolve::check_class(class, signatures);
+    diagnostics.extend(typeck::check_class(class, signatures, leniency));
+    diagnostics.extend(flow::check_class(class));
+    diagnostics.extend(lint::check_class(class, file_stem, lints));
+    diagnostics.sort_by_key(|d| d.span.start);
+    diagnostics
+}
+
+/// Compiles a program given as the file stem and source of each class, e.g.
+/// to run it in the emulator. Lints run at their default levels, so only
+/// errors fail a file. On failure, returns each failed file's diagnostics.
+pub fn compile_program(sources: &[(&str, &str)]) -> Result<Vec<VmFile>, Vec<(String, Vec<Diagnostic>)>> {
+    let parsed: Vec<(&str, ClassNode, Vec<Diagnostic>)> = sources
+        .iter()
+        .map(|&(name, source)| {
+            let (tokens, mut diagnostics) = tokenizer::tokenizer(source);
+            let (ast, parse_errors) = Parser::new(&tokens).parse_class();
+            diagnostics.extend(parse_errors);
+            (name, ast, diagnostics)
+        })
+        .collect();
+    let signatures = ProgramSignatures::from_classes(parsed.iter().map(|(_, ast, _)| ast));
+
+    let mut vm_files = Vec::new();
+    let mut failures = Vec::new();
+    for (name, ast, mut diagnostics) in parsed {
+        if diagnostics.is_empty() {
+            diagnostics = check_class(&ast, name, &signatures, Leniency::Lenient, &LintConfig::default());
+        }
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            failures.push((name.to_string(), diagnostics));
+            continue;
+        }
+        match CodeGenerator::new().compile_class(&ast) {
+            Ok(vm_code) => match VmFile::parse(name, &vm_code) {
+                Ok(vm_file) => vm_files.push(vm_file),
+                Err(e) => failures.push((name.to_string(), vec![*e])),
+            },
+            Err(e) => failures.push((name.to_string(), vec![*e])),
+        }
+    }
+    if failures.is_empty() { Ok(vm_files) } else { Err(failures) }
+}
@@ -0,0 +1,442 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::flow;
+use crate::parser::{ClassNode, ClassVarKind, ExpressionNode, StatementNode, SubroutineCallNode, TermKind, TermNode, Type};
+use crate::span::Span;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    /// Reported as an error, so the file fails to compile
+    Deny,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// A style or correctness rule. Unlike hard errors, lints can be turned off
+/// or made fatal. The name doubles as the diagnostic code.
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    /// What the lint flags, in a short sentence
+    pub description: &'static str,
+    pub default_level: Level,
+}
+
+pub const UNUSED_LOCAL: Lint = Lint { name: "unused_local", description: "A local variable that is never read", default_level: Level::Warn };
+pub const UNINITIALIZED_LOCAL: Lint = Lint { name: "uninitialized_local", description: "A local variable that may be read before it is assigned", default_level: Level::Warn };
+pub const UNUSED_PARAMETER: Lint = Lint { name: "unused_parameter", description: "A parameter that is never read", default_level: Level::Warn };
+pub const UNUSED_FIELD: Lint = Lint { name: "unused_field", description: "A field or static variable that is never used", default_level: Level::Warn };
+pub const SHADOWED_FIELD: Lint = Lint { name: "shadowed_field", description: "A parameter or local variable with the name of a field or static variable", default_level: Level::Warn };
+pub const UNREACHABLE_CODE: Lint = Lint { name: "unreachable_code", description: "Statements that can never run", default_level: Level::Warn };
+pub const EMPTY_BODY: Lint = Lint { name: "empty_body", description: "An `if`, `else` or `while` with no statements", default_level: Level::Warn };
+pub const CLASS_NAME_MISMATCH: Lint = Lint { name: "class_name_mismatch", description: "A class declared in a file of another name", default_level: Level::Warn };
+
+pub const LINTS: &[Lint] = &[
+    UNUSED_LOCAL,
+    UNINITIALIZED_LOCAL,
+    UNUSED_PARAMETER,
+    UNUSED_FIELD,
+    SHADOWED_FIELD,
+    UNREACHABLE_CODE,
+    EMPTY_BODY,
+    CLASS_NAME_MISMATCH,
+];
+
+/// Lint levels that differ from the defaults.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, Level>,
+}
+
+impl LintConfig {
+    /// The project file looked for in the directory being compiled.
+    pub const FILE_NAME: &str = "jack.toml";
+
+    /// Parses the `[lints]` table of the project file at `path`, e.g.
+    ///
+    /// ```toml
+    /// [lints]
+    /// unused_parameter = "allow"
+    /// unreachable_code = "deny"
+    /// ```
+    ///
+    /// Other tables are ignored.
+    pub fn parse(path: &Path, content: &str) -> Result<Self, String> {
+        let mut config = LintConfig::default();
+        let mut in_lints = false;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                in_lints = table.trim() == "lints";
+                continue;
+            }
+            if !in_lints {
+                continue;
+            }
+            let error = |message: String| format!("{}:{}: {}", path.display(), i + 1, message);
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(error(format!("expected `<lint> = \"<level>\"`, found `{}`", line)));
+            };
+            let value = value.trim().trim_matches('"');
+            let level = Level::parse(value).ok_or_else(|| error(format!("unknown lint level `{}`, expected allow, warn or deny", value)))?;
+            config.set(name.trim(), level).map_err(error)?;
+        }
+        Ok(config)
+    }
+
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+        let lint = LINTS.iter().find(|lint| lint.name == name).ok_or_else(|| format!("unknown lint `{}`", name))?;
+        self.levels.insert(lint.name, level);
+        Ok(())
+    }
+
+    pub fn level(&self, lint: &Lint) -> Level {
+        self.levels.get(lint.name).copied().unwrap_or(lint.default_level)
+    }
+}
+
+/// Runs every lint that isn't allowed over `class`, which was read from a file named `file_stem`.jack.
+pub fn check_class(class: &ClassNode, file_stem: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter { config, diagnostics: Vec::new() };
+
+    if class.name != file_stem {
+        linter.report(
+            &CLASS_NAME_MISMATCH,
+            Diagnostic::warning(CLASS_NAME_MISMATCH.name, format!("class `{}` is declared in `{}.jack`", class.name, file_stem), class.span)
+                .with_help(format!("rename the file to `{}.jack`", class.name)),
+        );
+    }
+
+    let class_vars: HashMap<&str, _> = class.var_decs.iter()
+        .flat_map(|var_dec| var_dec.names.iter().map(move |name| (name.as_str(), var_dec)))
+        .collect();
+    let mut used_class_vars: HashSet<&str> = HashSet::new();
+
+    for subroutine in &class.subroutine_decs {
+        let mut uses = Uses::default();
+        uses.statements(&subroutine.body.statements);
+
+        let locals: Vec<_> = subroutine.body.var_decs.iter()
+            .flat_map(|var_dec| var_dec.names.iter().map(move |name| (name.as_str(), var_dec.span)))
+            .collect();
+        let in_scope: HashSet<&str> = subroutine.parameters.iter().map(|param| param.name.as_str())
+            .chain(locals.iter().map(|(name, _)| *name))
+            .collect();
+        used_class_vars.extend(uses.read.iter().chain(&uses.written).filter(|name| !in_scope.contains(*name)));
+
+        for param in &subroutine.parameters {
+            let name = &param.name;
+            if !uses.read.contains(name.as_str()) {
+                linter.report(&UNUSED_PARAMETER, Diagnostic::warning(UNUSED_PARAMETER.name, format!("unused parameter `{}`", name), param.span));
+            }
+            if let Some(var_dec) = class_vars.get(name.as_str()) {
+                linter.report(
+                    &SHADOWED_FIELD,
+                    Diagnostic::warning(SHADOWED_FIELD.name, format!("parameter `{}` shadows the {} of the same name", name, class_var_kind(&var_dec.kind)), param.span)
+                        .with_label(var_dec.span, "declared here"),
+                );
+            }
+        }
+        for (name, span) in &locals {
+            if !uses.read.contains(name) {
+                let message = if uses.written.contains(name) {
+                    format!("local variable `{}` is assigned but never read", name)
+                } else {
+                    format!("unused local variable `{}`", name)
+                };
+                linter.report(&UNUSED_LOCAL, Diagnostic::warning(UNUSED_LOCAL.name, message, *span));
+            }
+            if let Some(var_dec) = class_vars.get(name) {
+                linter.report(
+                    &SHADOWED_FIELD,
+                    Diagnostic::warning(SHADOWED_FIELD.name, format!("local variable `{}` shadows the {} of the same name", name, class_var_kind(&var_dec.kind)), *span)
+                        .with_label(var_dec.span, "declared here"),
+                );
+            }
+        }
+
+        let mut assignments = Assignments {
+            locals: subroutine.body.var_decs.iter()
+                .flat_map(|var_dec| var_dec.names.iter().map(move |name| (name.as_str(), &var_dec.var_type)))
+                .collect(),
+            early_reads: Vec::new(),
+        };
+        assignments.statements(&subroutine.body.statements, HashSet::new());
+        for (name, span) in assignments.early_reads {
+            let initial_value = match assignments.locals[name] {
+                Type::ClassName(_) => "`null`",
+                Type::Int | Type::Char | Type::Boolean => "0",
+            };
+            linter.report(
+                &UNINITIALIZED_LOCAL,
+                Diagnostic::warning(UNINITIALIZED_LOCAL.name, format!("local variable `{}` may be read before it is assigned", name), span)
+                    .with_note(format!("`{}` is {} until it is first assigned", name, initial_value)),
+            );
+        }
+
+        linter.statements(&subroutine.body.statements);
+    }
+
+    for var_dec in &class.var_decs {
+        for name in &var_dec.names {
+            if !used_class_vars.contains(name.as_str()) {
+                linter.report(
+                    &UNUSED_FIELD,
+                    Diagnostic::warning(UNUSED_FIELD.name, format!("{} `{}` is never used", class_var_kind(&var_dec.kind), name), var_dec.span),
+                );
+            }
+        }
+    }
+
+    linter.diagnostics
+}
+
+fn class_var_kind(kind: &ClassVarKind) -> &'static str {
+    match kind {
+        ClassVarKind::Static => "static variable",
+        ClassVarKind::Field => "field",
+    }
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    // `diagnostic` is built as a warning and promoted or dropped according to the lint's level
+    fn report(&mut self, lint: &Lint, diagnostic: Diagnostic) {
+        match self.config.level(lint) {
+            Level::Allow => {}
+            Level::Warn => self.diagnostics.push(diagnostic),
+            Level::Deny => self.diagnostics.push(Diagnostic { severity: Severity::Error, ..diagnostic }),
+        }
+    }
+
+    // The lints that look at statement blocks: unreachable code and empty bodies
+    fn statements(&mut self, statements: &[StatementNode]) {
+        for (i, statement) in statements.iter().enumerate() {
+            match statement {
+                StatementNode::If(if_stmt) => {
+                    if if_stmt.if_block.is_empty() {
+                        self.report(&EMPTY_BODY, Diagnostic::warning(EMPTY_BODY.name, "empty `if` body", if_stmt.span));
+                    }
+                    self.statements(&if_stmt.if_block);
+                    if let Some(else_block) = &if_stmt.else_block {
+                        if else_block.is_empty() {
+                            self.report(&EMPTY_BODY, Diagnostic::warning(EMPTY_BODY.name, "empty `else` body", if_stmt.span));
+                        }
+                        self.statements(else_block);
+                    }
+                }
+                StatementNode::While(while_stmt) => {
+                    if while_stmt.body.is_empty() {
+                        self.report(&EMPTY_BODY, Diagnostic::warning(EMPTY_BODY.name, "empty `while` body", while_stmt.span));
+                    }
+                    self.statements(&while_stmt.body);
+                }
+                StatementNode::Let(_) | StatementNode::Do(_) | StatementNode::Return(_) => {}
+            }
+
+            // Only the first unreachable statement of a block is reported, covering the rest of it
+            let rest = &statements[i + 1..];
+            if let (Some(next), Some(last)) = (rest.first(), rest.last())
+                && flow::always_returns(std::slice::from_ref(statement))
+            {
+                self.report(
+                    &UNREACHABLE_CODE,
+                    Diagnostic::warning(UNREACHABLE_CODE.name, "unreachable statement", next.span().to(last.span()))
+                        .with_label(statement.span(), "any code following this statement is unreachable"),
+                );
+                break;
+            }
+        }
+    }
+}
+
+/// The variable names a subroutine body reads and writes. Writes are only
+/// plain `let x = ...`; `let a[i] = ...` reads `a`.
+#[derive(Default)]
+struct Uses<'a> {
+    read: HashSet<&'a str>,
+    written: HashSet<&'a str>,
+}
+
+impl<'a> Uses<'a> {
+    fn statements(&mut self, statements: &'a [StatementNode]) {
+        for statement in statements {
+            match statement {
+                StatementNode::Let(let_stmt) => {
+                    match &let_stmt.index_expr {
+                        Some(index_expr) => {
+                            self.read.insert(&let_stmt.var_name);
+                            self.expression(index_expr);
+                        }
+                        None => {
+                            self.written.insert(&let_stmt.var_name);
+                        }
+                    }
+                    self.expression(&let_stmt.value_expr);
+                }
+                StatementNode::If(if_stmt) => {
+                    self.expression(&if_stmt.condition);
+                    self.statements(&if_stmt.if_block);
+                    if let Some(else_block) = &if_stmt.else_block {
+                        self.statements(else_block);
+                    }
+                }
+                StatementNode::While(while_stmt) => {
+                    self.expression(&while_stmt.condition);
+                    self.statements(&while_stmt.body);
+                }
+                StatementNode::Do(do_stmt) => self.call(&do_stmt.call),
+                StatementNode::Return(return_stmt) => {
+                    if let Some(value) = &return_stmt.value {
+                        self.expression(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &'a ExpressionNode) {
+        self.term(&expr.initial_term);
+        for (_, term) in &expr.operations {
+            self.term(term);
+        }
+    }
+
+    fn term(&mut self, term: &'a TermNode) {
+        match &term.kind {
+            TermKind::IntConst(_) | TermKind::StrConst(_) | TermKind::KeywordConst(_) => {}
+            TermKind::VarName(name) => {
+                self.read.insert(name);
+            }
+            TermKind::ArrayAccess(name, index_expr) => {
+                self.read.insert(name);
+                self.expression(index_expr);
+            }
+            TermKind::SubroutineCall(call) => self.call(call),
+            TermKind::Parenthesized(expr) => self.expression(expr),
+            TermKind::UnaryOp(_, inner) => self.term(inner),
+        }
+    }
+
+    fn call(&mut self, call: &'a SubroutineCallNode) {
+        // A class name as receiver is harmless here, it never matches a variable
+        if let Some(receiver) = &call.receiver {
+            self.read.insert(receiver);
+        }
+        for arg in &call.args {
+            self.expression(arg);
+        }
+    }
+}
+
+/// Definite assignment of locals: walks a subroutine body tracking which
+/// locals are assigned on every path so far, and records the first read of
+/// each local that happens while it may still be unassigned.
+struct Assignments<'a> {
+    locals: HashMap<&'a str, &'a Type>,
+    early_reads: Vec<(&'a str, Span)>,
+}
+
+impl<'a> Assignments<'a> {
+    // Takes the locals assigned before `statements` and returns those assigned after them
+    fn statements(&mut self, statements: &'a [StatementNode], mut assigned: HashSet<&'a str>) -> HashSet<&'a str> {
+        for statement in statements {
+            match statement {
+                StatementNode::Let(let_stmt) => {
+                    if let Some(index_expr) = &let_stmt.index_expr {
+                        self.read(&let_stmt.var_name, let_stmt.name_span, &assigned);
+                        self.expression(index_expr, &assigned);
+                    }
+                    self.expression(&let_stmt.value_expr, &assigned);
+                    if let_stmt.index_expr.is_none() && self.locals.contains_key(let_stmt.var_name.as_str()) {
+                        assigned.insert(&let_stmt.var_name);
+                    }
+                }
+                StatementNode::If(if_stmt) => {
+                    self.expression(&if_stmt.condition, &assigned);
+                    let after_if = self.statements(&if_stmt.if_block, assigned.clone());
+                    let after_else = match &if_stmt.else_block {
+                        Some(else_block) => self.statements(else_block, assigned.clone()),
+                        None => assigned.clone(),
+                    };
+                    // A branch that always returns doesn't reach the code after the `if`
+                    let if_returns = flow::always_returns(&if_stmt.if_block);
+                    let else_returns = if_stmt.else_block.as_deref().is_some_and(flow::always_returns);
+                    assigned = match (if_returns, else_returns) {
+                        (true, false) => after_else,
+                        (false, true) => after_if,
+                        _ => after_if.intersection(&after_else).copied().collect(),
+                    };
+                }
+                StatementNode::While(while_stmt) => {
+                    self.expression(&while_stmt.condition, &assigned);
+                    // The body may not run at all, so nothing it assigns is certain afterwards
+                    self.statements(&while_stmt.body, assigned.clone());
+                }
+                StatementNode::Do(do_stmt) => self.call(&do_stmt.call, &assigned),
+                StatementNode::Return(return_stmt) => {
+                    if let Some(value) = &return_stmt.value {
+                        self.expression(value, &assigned);
+                    }
+                }
+            }
+        }
+        assigned
+    }
+
+    fn expression(&mut self, expr: &'a ExpressionNode, assigned: &HashSet<&'a str>) {
+        self.term(&expr.initial_term, assigned);
+        for (_, term) in &expr.operations {
+            self.term(term, assigned);
+        }
+    }
+
+    fn term(&mut self, term: &'a TermNode, assigned: &HashSet<&'a str>) {
+        match &term.kind {
+            TermKind::IntConst(_) | TermKind::StrConst(_) | TermKind::KeywordConst(_) => {}
+            TermKind::VarName(name) => self.read(name, term.span, assigned),
+            TermKind::ArrayAccess(name, index_expr) => {
+                self.read(name, term.span, assigned);
+                self.expression(index_expr, assigned);
+            }
+            TermKind::SubroutineCall(call) => self.call(call, assigned),
+            TermKind::Parenthesized(expr) => self.expression(expr, assigned),
+            TermKind::UnaryOp(_, inner) => self.term(inner, assigned),
+        }
+    }
+
+    fn call(&mut self, call: &'a SubroutineCallNode, assigned: &HashSet<&'a str>) {
+        if let (Some(receiver), Some(receiver_span)) = (&call.receiver, call.receiver_span) {
+            self.read(receiver, receiver_span, assigned);
+        }
+        for arg in &call.args {
+            self.expression(arg, assigned);
+        }
+    }
+
+    fn read(&mut self, name: &'a str, span: Span, assigned: &HashSet<&'a str>) {
+        let already_reported = self.early_reads.iter().any(|(read, _)| *read == name);
+        if self.locals.contains_key(name) && !assigned.contains(name) && !already_reported {
+            self.early_reads.push((name, span));
+        }
+    }
+}
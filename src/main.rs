@@ -1,100 +1,671 @@
-mod tokenizer;
-mod parser;
+mod cli;
 
-use std::{env, fs, path::Path};
-use tokenizer::{tokenizer, Token};
-use parser::{Parser, ClassNode};
+use std::{env, fs, io::{self, IsTerminal, Write}, path::{Path, PathBuf}, process::ExitCode};
+use jack_compiler::{assembler, disassembler, json, pretty, report, sarif, translate, xml};
+use jack_compiler::tokenizer::{comments, tokenizer, Token, TokenType};
+use jack_compiler::parser::{Parser, ClassNode};
+use jack_compiler::codegen::CodeGenerator;
+use jack_compiler::diagnostic::{codes, Diagnostic, Severity};
+use jack_compiler::span::code_lines;
+use jack_compiler::signatures::ProgramSignatures;
+use jack_compiler::typeck::Leniency;
+use jack_compiler::lint::{Level, LintConfig};
+use jack_compiler::vm::VmFile;
+use jack_compiler::emulator::{Emulator, Stop};
+use cli::{Cli, ColorChoice, Command, Emit, MessageFormat};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// Exit codes, also listed in the help text. When files fail for different
+// reasons the highest code wins.
+const EXIT_COMPILE_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_IO_ERROR: u8 = 3;
+const EXIT_RUNTIME_ERROR: u8 = 4;
 
-    // Check if path was provided
-    if args.len() < 2 {
-        println!("Usage: {} <file_or_directory>", args[0]);
-        return;
+struct Options {
+    /// What `compile` and `translate` write, empty for the other commands
+    emit: Vec<Emit>,
+    out_dir: Option<PathBuf>,
+    color: bool,
+    message_format: MessageFormat,
+    /// Write symbol maps next to machine code, or read them to disassemble it
+    symbols: bool,
+    leniency: Leniency,
+    lints: LintConfig,
+    /// Start the assembly of the program with the bootstrap code
+    bootstrap: bool,
+}
+
+/// A file that has been read and parsed, along with the diagnostics found so far.
+struct SourceFile {
+    path: PathBuf,
+    content: String,
+    tokens: Vec<Token>,
+    ast: ClassNode,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Why the whole run or a single file failed.
+enum Failure {
+    /// Bad arguments or configuration
+    Usage(String),
+    /// A file or directory couldn't be read or written
+    Io(String),
+    /// The file has errors. The diagnostics include any warnings reported with them.
+    Compile { rendered: String, diagnostics: Vec<Diagnostic> },
+    /// The files compiled but don't form a complete program
+    Link(String),
+    /// The program failed in the emulator or ran out of steps
+    Runtime(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Usage(_) => EXIT_USAGE,
+            Failure::Io(_) => EXIT_IO_ERROR,
+            Failure::Compile { .. } | Failure::Link(_) => EXIT_COMPILE_ERROR,
+            Failure::Runtime(_) => EXIT_RUNTIME_ERROR,
+        }
     }
 
-    let path = Path::new(&args[1]);
+    fn report(&self, message_format: MessageFormat) {
+        match self {
+            Failure::Usage(message) | Failure::Io(message) | Failure::Link(message) | Failure::Runtime(message) => {
+                eprintln!("error: {}", message)
+            }
+            Failure::Compile { rendered, .. } => print_diagnostics(rendered, message_format),
+        }
+    }
+}
 
-    // Check if path exists
-    if !path.exists() {
-        println!("Path '{}' does not exist", path.display());
-        return;
+/// What was done for an input file that didn't fail.
+struct Compiled {
+    written: Vec<PathBuf>,
+    /// Warnings, which have already been printed
+    diagnostics: Vec<Diagnostic>,
+    /// The generated code, for the outputs built from the whole program
+    vm_code: Option<String>,
+}
+
+impl Compiled {
+    fn written(written: Vec<PathBuf>) -> Self {
+        Compiled { written, diagnostics: Vec::new(), vm_code: None }
     }
+}
 
-    if path.is_dir() {
-        println!("Operating on Directory: {}", path.display());
-        match fs::read_dir(path) {
-            Ok(entries) => {
-                for entry in entries {
-                    match entry {
-                        Ok(entry) => {
-                            let file_path = entry.path();
-                            if file_path.extension().and_then(|s| s.to_str()) == Some("jack") {
-                                println!("--- Processing file: {} ---", file_path.display());
-                                process_file(&file_path);
-                            }
-                        }
-                        Err(e) => {
-                            println!("Error in reading dir entry: {}", e);
-                            return;
-                        }
+type Outcome = Result<Compiled, Failure>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let code = match cli::parse_args(&args[1..]) {
+        Ok(Some(cli)) => run(cli).unwrap_or_else(|failure| {
+            failure.report(MessageFormat::Human);
+            failure.exit_code()
+        }),
+        Ok(None) => {
+            print!("{}", cli::help());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}\n{}\nRun with --help for more information.", e, cli::USAGE);
+            EXIT_USAGE
+        }
+    };
+    ExitCode::from(code)
+}
+
+// Returns the exit code for the files' outcomes
+fn run(cli: Cli) -> Result<u8, Failure> {
+    if !cli.path.exists() {
+        return Err(Failure::Io(format!("path '{}' does not exist", cli.path.display())));
+    }
+
+    let extension = match cli.command {
+        Command::Assemble => "asm",
+        Command::Disassemble => "hack",
+        Command::Translate => "vm",
+        _ => "jack",
+    };
+    let source_files = if cli.path.is_dir() {
+        collect_files(&cli.path, extension).map_err(|e| Failure::Io(format!("could not read the directory {}: {}", cli.path.display(), e)))?
+    } else {
+        vec![cli.path.clone()]
+    };
+    if let Some(out_dir) = &cli.out_dir {
+        fs::create_dir_all(out_dir)
+            .map_err(|e| Failure::Io(format!("could not create the output directory {}: {}", out_dir.display(), e)))?;
+    }
+
+    // Diagnostics go to stderr, so that is the stream that decides on color
+    let color = match cli.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    let options = Options {
+        emit: match cli.command {
+            Command::Compile => cli.emit,
+            Command::Translate => vec![Emit::Asm],
+            _ => Vec::new(),
+        },
+        out_dir: cli.out_dir,
+        color,
+        message_format: cli.message_format,
+        symbols: cli.symbols,
+        leniency: cli.leniency,
+        lints: load_lint_config(&cli.path, &cli.lint_levels)?,
+        bootstrap: cli.bootstrap,
+    };
+
+    // Outputs printed to stdout get a header per file once there is more than one
+    let with_headers = source_files.len() > 1;
+    let mut outcomes = Vec::new();
+    match cli.command {
+        Command::Tokenize => {
+            for file_path in &source_files {
+                outcomes.push((file_path, tokenize_file(file_path, &options, with_headers)));
+            }
+        }
+        Command::Parse | Command::Fmt => {
+            for file_path in &source_files {
+                let outcome = parse_file(file_path).and_then(|source| {
+                    let comments = comments(&source.content);
+                    let ast = successful_parse(source, &options)?;
+                    let (suffix, text) = match cli.command {
+                        Command::Parse => (".ast", ast_listing(&ast)),
+                        _ => (".jack", pretty::format_class(&ast, &comments)),
                     };
-                }
+                    print_or_write(file_path, suffix, &text, &options, with_headers).map(Compiled::written)
+                });
+                outcomes.push((file_path, outcome));
             }
-            Err(e) => {
-                println!("Error in reading directory: {}", e);
-                return;
+        }
+        Command::Translate => {
+            for file_path in &source_files {
+                outcomes.push((file_path, read_vm_file(file_path, &options)));
+            }
+        }
+        Command::Assemble => {
+            for file_path in &source_files {
+                outcomes.push((file_path, assemble_file(file_path, &options)));
+            }
+        }
+        Command::Disassemble => {
+            for file_path in &source_files {
+                outcomes.push((file_path, disassemble_file(file_path, &options, with_headers)));
             }
         }
-    } else if path.is_file() {
-        println!("Operating on file: {}", path.display());
-        process_file(path);
+        Command::Check | Command::Compile | Command::Run => {
+            // Parse every file before checking any, so that calls can be checked against all classes of the program
+            let parsed: Vec<_> = source_files.iter().map(|file_path| (file_path, parse_file(file_path))).collect();
+            let signatures = ProgramSignatures::from_classes(parsed.iter().filter_map(|(_, result)| result.as_ref().ok()).map(|source| &source.ast));
+
+            for (file_path, result) in parsed {
+                outcomes.push((file_path, result.and_then(|source| compile_file(source, &signatures, &options))));
+            }
+        }
+    }
+
+    // Assembly, machine code and running are for the whole program, so they need every file to have compiled
+    let compiled = outcomes.iter().all(|(_, outcome)| outcome.is_ok());
+    let program = if cli.command == Command::Run {
+        compiled.then(|| (&cli.path, run_program(&cli.path, &outcomes, &options, cli.max_steps)))
+    } else {
+        let linked = options.emit.iter().any(|kind| matches!(kind, Emit::Asm | Emit::Hack));
+        (linked && compiled).then(|| (&cli.path, link_program(&cli.path, &outcomes, &options).map(Compiled::written)))
+    };
+
+    let mut code = 0;
+    for (_, outcome) in outcomes.iter().chain(&program) {
+        if let Err(failure) = outcome {
+            failure.report(options.message_format);
+            code = code.max(failure.exit_code());
+        }
+    }
+    if options.message_format == MessageFormat::Json {
+        print_summary_record(&outcomes, program.as_ref());
+    } else if matches!(cli.command, Command::Check | Command::Compile | Command::Translate | Command::Assemble) {
+        let verb = match cli.command {
+            Command::Check => "checked",
+            Command::Translate => "translated",
+            Command::Assemble => "assembled",
+            _ => "compiled",
+        };
+        print_summary(&outcomes, program.as_ref(), verb);
     }
+    if let Some(sarif_path) = &cli.sarif {
+        write_sarif(sarif_path, outcomes.iter().chain(&program), &options.lints)?;
+    }
+    Ok(code)
 }
 
-fn process_file(file_path: &Path) {
-    let content = match fs::read_to_string(file_path) {
-        Ok(c) => c,
-        Err(e) => {
-            println!("Could not read the file {}: {}", file_path.display(), e);
-            return;
+// Lint levels come from the project file next to the sources, if there is one, and then from the command line
+fn load_lint_config(path: &Path, lint_levels: &[(String, Level)]) -> Result<LintConfig, Failure> {
+    let project_dir = if path.is_dir() { Some(path) } else { path.parent() };
+    let project_file = project_dir.map(|dir| dir.join(LintConfig::FILE_NAME)).filter(|file| file.is_file());
+    let mut lints = match project_file {
+        Some(file) => {
+            let content = fs::read_to_string(&file)
+                .map_err(|e| Failure::Io(format!("could not read the file {}: {}", file.display(), e)))?;
+            LintConfig::parse(&file, &content).map_err(Failure::Usage)?
         }
+        None => LintConfig::default(),
     };
+    for (lint, level) in lint_levels {
+        lints.set(lint, *level).map_err(Failure::Usage)?;
+    }
+    Ok(lints)
+}
+
+// Sorted so that output and summaries don't depend on the file system's listing order
+fn collect_files(dir: &Path, extension: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let file_path = entry?.path();
+        if file_path.is_file() && file_path.extension().and_then(|s| s.to_str()) == Some(extension) {
+            files.push(file_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Prints or writes the tokens of a single file. Lexical errors are reported
+/// but the tokens are still listed, with the bad input as `error` tokens.
+fn tokenize_file(file_path: &Path, options: &Options, with_header: bool) -> Outcome {
+    let content = read_source(file_path)?;
+    let (tokens, diagnostics) = tokenizer(&content);
+    let written = print_or_write(file_path, ".tokens", &token_listing(&tokens), options, with_header)?;
+    if !diagnostics.is_empty() {
+        let rendered = render_diagnostics(&diagnostics, file_path, &content, options);
+        return Err(Failure::Compile { rendered, diagnostics });
+    }
+    Ok(Compiled::written(written))
+}
+
+/// Reads, tokenizes and parses a single `.jack` file.
+fn parse_file(file_path: &Path) -> Result<SourceFile, Failure> {
+    let content = read_source(file_path)?;
 
     // 1. Tokenize
-    let tokens = match tokenizer(&content) {
-        Ok(t) => t,
+    let (tokens, mut diagnostics) = tokenizer(&content);
+
+    // 2. Parse, even after lexical errors so that syntax errors are reported too
+    let mut parser = Parser::new(&tokens);
+    let (ast, parse_errors) = parser.parse_class();
+    diagnostics.extend(parse_errors);
+    diagnostics.sort_by_key(|d| d.span.start);
+
+    Ok(SourceFile { path: file_path.to_path_buf(), content, tokens, ast, diagnostics })
+}
+
+// The tree of a file that parsed without errors, or the rendered errors
+fn successful_parse(source: SourceFile, options: &Options) -> Result<ClassNode, Failure> {
+    if source.diagnostics.is_empty() {
+        return Ok(source.ast);
+    }
+    let rendered = render_diagnostics(&source.diagnostics, &source.path, &source.content, options);
+    Err(Failure::Compile { rendered, diagnostics: source.diagnostics })
+}
+
+/// Checks a parsed file and writes the outputs selected with `--emit`, next
+/// to the source or into the output directory.
+fn compile_file(mut source: SourceFile, signatures: &ProgramSignatures, options: &Options) -> Outcome {
+    let mut written = Vec::new();
+
+    // 3. Resolve names, check types and returns and run the lints, unless the tree is only partial because of syntax errors
+    if source.diagnostics.is_empty() {
+        for kind in &options.emit {
+            let outputs = match kind {
+                Emit::Tokens => vec![(".tokens", token_listing(&source.tokens))],
+                Emit::Xml => vec![("T.xml", xml::tokens_to_xml(&source.tokens)), (".xml", xml::class_to_xml(&source.ast))],
+                Emit::Ast => vec![(".ast", ast_listing(&source.ast))],
+                // Only written once the file has passed the checks
+                Emit::Vm | Emit::Asm | Emit::Hack => Vec::new(),
+            };
+            for (suffix, content) in outputs {
+                let path = output_path(&source.path, options.out_dir.as_deref(), suffix);
+                write_output(&path, &content)?;
+                written.push(path);
+            }
+        }
+
+        let file_stem = source.path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        source.diagnostics = jack_compiler::check_class(&source.ast, file_stem, signatures, options.leniency, &options.lints);
+    }
+    let rendered = render_diagnostics(&source.diagnostics, &source.path, &source.content, options);
+    if count(&source.diagnostics, Severity::Error) > 0 {
+        return Err(Failure::Compile { rendered, diagnostics: source.diagnostics });
+    }
+    // Warnings alone don't stop the file from compiling
+    print_diagnostics(&rendered, options.message_format);
+
+    // 4. Generate VM code
+    let mut generator = CodeGenerator::new();
+    let vm_code = match generator.compile_class(&source.ast) {
+        Ok(vm_code) => vm_code,
         Err(e) => {
-            println!("Tokenizer error in {}: {}", file_path.display(), e);
-            return;
+            let rendered = render_diagnostics(std::slice::from_ref(&e), &source.path, &source.content, options);
+            source.diagnostics.push(*e);
+            return Err(Failure::Compile { rendered, diagnostics: source.diagnostics });
         }
     };
-    debug_tokens(&tokens);
 
-    // 2. Parse
-    let mut parser = Parser::new(&tokens);
-    match parser.parse_class() {
-        Ok(ast) => {
-            debug_ast(&ast);
+    // 5. Write Foo.vm
+    if options.emit.contains(&Emit::Vm) {
+        let vm_path = output_path(&source.path, options.out_dir.as_deref(), ".vm");
+        write_output(&vm_path, &vm_code)?;
+        written.push(vm_path);
+    }
+    Ok(Compiled { written, diagnostics: source.diagnostics, vm_code: Some(vm_code) })
+}
+
+// Rustc-style snippets, or one JSON record per line
+fn render_diagnostics(diagnostics: &[Diagnostic], path: &Path, content: &str, options: &Options) -> String {
+    let rendered: Vec<String> = match options.message_format {
+        MessageFormat::Human => diagnostics.iter().map(|d| report::render(d, path, content, options.color)).collect(),
+        MessageFormat::Json => diagnostics.iter().map(|d| json::diagnostic_record(d, path).to_string()).collect(),
+    };
+    rendered.join("\n")
+}
+
+// JSON records go to stdout with the summary record, so that a consumer needs to read only one stream
+fn print_diagnostics(rendered: &str, message_format: MessageFormat) {
+    if rendered.is_empty() {
+        return;
+    }
+    match message_format {
+        MessageFormat::Human => eprintln!("{}", rendered),
+        MessageFormat::Json => println!("{}", rendered),
+    }
+}
+
+/// Translates the compiled files into one `.asm` file, and assembles that into
+/// a `.hack` file, named after the directory being compiled. Other `.vm` files
+/// in the directory, such as the OS, are linked in too.
+fn link_program(path: &Path, outcomes: &[(&PathBuf, Outcome)], options: &Options) -> Result<Vec<PathBuf>, Failure> {
+    let vm_files = program_vm_files(path, outcomes, options)?;
+    let asm = translate::translate_program(&vm_files, options.bootstrap).map_err(|e| Failure::Compile {
+        rendered: render_diagnostics(std::slice::from_ref(&e), path, "", options),
+        diagnostics: vec![*e],
+    })?;
+    let asm_path = program_output_path(path, options.out_dir.as_deref(), ".asm");
+    let mut written = Vec::new();
+    if options.emit.contains(&Emit::Asm) {
+        write_output(&asm_path, &asm)?;
+        written.push(asm_path.clone());
+    }
+    if options.emit.contains(&Emit::Hack) {
+        // Only a program too large for the ROM gets here
+        let (words, symbols) = assembler::assemble(&asm).map_err(|diagnostics| Failure::Compile {
+            rendered: render_diagnostics(&diagnostics, &asm_path, &asm, options),
+            diagnostics,
+        })?;
+        let hack_path = program_output_path(path, options.out_dir.as_deref(), ".hack");
+        write_output(&hack_path, &assembler::to_text(&words))?;
+        written.push(hack_path);
+        if options.symbols {
+            let sym_path = program_output_path(path, options.out_dir.as_deref(), ".sym");
+            write_output(&sym_path, &symbols.to_text())?;
+            written.push(sym_path);
         }
-        Err(e) => {
-            println!("Parser error in {}: {}", file_path.display(), e);
+    }
+    Ok(written)
+}
+
+/// Runs the compiled program in the emulator from `Sys.init`, with the
+/// console as its screen and keyboard. The OS is built in, so only its
+/// classes the program replaces need `.vm` files.
+fn run_program(path: &Path, outcomes: &[(&PathBuf, Outcome)], options: &Options, max_steps: Option<u64>) -> Outcome {
+    let vm_files = program_vm_files(path, outcomes, options)?;
+    let mut emulator = Emulator::new(&vm_files).map_err(Failure::Link)?;
+    emulator.set_console(Box::new(io::stdin().lock()), Box::new(io::stdout()));
+    emulator.start("Sys.init").map_err(Failure::Link)?;
+    match emulator.run(max_steps).map_err(Failure::Runtime)? {
+        Stop::Halted => {
+            eprintln!("halted after {} steps", emulator.steps());
+            Ok(Compiled::written(Vec::new()))
+        }
+        Stop::StepLimit => Err(Failure::Runtime(format!("stopped after {} steps, the limit set with --max-steps", emulator.steps()))),
+    }
+}
+
+// The compiled files, and any other `.vm` files in the directory being compiled
+fn program_vm_files(path: &Path, outcomes: &[(&PathBuf, Outcome)], options: &Options) -> Result<Vec<VmFile>, Failure> {
+    let mut vm_files = Vec::new();
+    for (jack_path, outcome) in outcomes {
+        if let Ok(Compiled { vm_code: Some(vm_code), .. }) = outcome {
+            let name = jack_path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+            let vm_file = VmFile::parse(name, vm_code)
+                .map_err(|e| Failure::Link(format!("the VM code compiled from {} is invalid: {}", jack_path.display(), e)))?;
+            vm_files.push(vm_file);
+        }
+    }
+    if path.is_dir() {
+        for vm_path in collect_files(path, "vm").map_err(|e| Failure::Io(format!("could not read the directory {}: {}", path.display(), e)))? {
+            let name = vm_path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+            // Left over from an earlier `--emit=vm`
+            if vm_files.iter().any(|file| file.name == name) {
+                continue;
+            }
+            let content = read_source(&vm_path)?;
+            let vm_file = VmFile::parse(name, &content).map_err(|e| Failure::Compile {
+                rendered: render_diagnostics(std::slice::from_ref(&e), &vm_path, &content, options),
+                diagnostics: vec![*e],
+            })?;
+            vm_files.push(vm_file);
         }
     }
+    Ok(vm_files)
+}
+
+/// Reads a single `.vm` file for `translate`, which links the files into one
+/// assembly file once they have all been read.
+fn read_vm_file(file_path: &Path, options: &Options) -> Outcome {
+    let content = read_source(file_path)?;
+    let name = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    if let Err(e) = VmFile::parse(name, &content) {
+        return Err(Failure::Compile {
+            rendered: render_diagnostics(std::slice::from_ref(&e), file_path, &content, options),
+            diagnostics: vec![*e],
+        });
+    }
+    Ok(Compiled { written: Vec::new(), diagnostics: Vec::new(), vm_code: Some(content) })
 }
 
+/// Assembles a single `.asm` file into `.hack` machine code, and a symbol map
+/// if asked for.
+fn assemble_file(file_path: &Path, options: &Options) -> Outcome {
+    let content = read_source(file_path)?;
+    let (words, symbols) = assembler::assemble(&content).map_err(|diagnostics| Failure::Compile {
+        rendered: render_diagnostics(&diagnostics, file_path, &content, options),
+        diagnostics,
+    })?;
+    let hack_path = output_path(file_path, options.out_dir.as_deref(), ".hack");
+    write_output(&hack_path, &assembler::to_text(&words))?;
+    let mut written = vec![hack_path];
+    if options.symbols {
+        let sym_path = output_path(file_path, options.out_dir.as_deref(), ".sym");
+        write_output(&sym_path, &symbols.to_text())?;
+        written.push(sym_path);
+    }
+    Ok(Compiled::written(written))
+}
+
+/// Prints or writes a single `.hack` file as assembly, with the names from
+/// the symbol map next to it if asked for.
+fn disassemble_file(file_path: &Path, options: &Options, with_header: bool) -> Outcome {
+    let content = read_source(file_path)?;
+    let invalid = |path: &Path, content: &str, e: Box<Diagnostic>| Failure::Compile {
+        rendered: render_diagnostics(std::slice::from_ref(&e), path, content, options),
+        diagnostics: vec![*e],
+    };
+    let words = assembler::parse_hack(&content).map_err(|e| invalid(file_path, &content, e))?;
+    let symbols = if options.symbols {
+        let sym_path = file_path.with_extension("sym");
+        let sym_content = read_source(&sym_path)?;
+        Some(assembler::SymbolMap::parse(&sym_content).map_err(|e| invalid(&sym_path, &sym_content, e))?)
+    } else {
+        None
+    };
+    let asm = disassembler::disassemble(&words, symbols.as_ref()).map_err(|invalid| {
+        let lines = code_lines(&content);
+        let diagnostics: Vec<Diagnostic> = invalid
+            .into_iter()
+            .map(|(i, reason)| {
+                Diagnostic::error(codes::INVALID_MACHINE_CODE, reason, lines[i].1)
+                    .with_note("no assembly assembles to this word, so it can't be disassembled")
+            })
+            .collect();
+        Failure::Compile { rendered: render_diagnostics(&diagnostics, file_path, &content, options), diagnostics }
+    })?;
+    print_or_write(file_path, ".asm", &asm, options, with_header).map(Compiled::written)
+}
+
+fn read_source(file_path: &Path) -> Result<String, Failure> {
+    fs::read_to_string(file_path)
+        .map_err(|e| Failure::Io(format!("could not read the file {}: {}", file_path.display(), e)))
+}
+
+// `tokenize`, `parse` and `fmt` print their output, or write it into the output directory when one is given
+fn print_or_write(file_path: &Path, suffix: &str, text: &str, options: &Options, with_header: bool) -> Result<Vec<PathBuf>, Failure> {
+    let Some(out_dir) = &options.out_dir else {
+        let mut stdout = io::stdout().lock();
+        if with_header {
+            let _ = writeln!(stdout, "==> {} <==", file_path.display());
+        }
+        // Failing here means stdout was closed early, e.g. by `| head`, and nobody wants the rest
+        let _ = stdout.write_all(text.as_bytes());
+        return Ok(Vec::new());
+    };
+    let path = output_path(file_path, Some(out_dir), suffix);
+    // `fmt --out-dir` pointing at the sources must not replace them
+    if fs::canonicalize(&path).ok() == fs::canonicalize(file_path).ok() {
+        return Err(Failure::Usage(format!("refusing to overwrite the source file {}", file_path.display())));
+    }
+    write_output(&path, text)?;
+    Ok(vec![path])
+}
+
+// `suffix` replaces the extension, e.g. `T.xml` turns Foo.jack into FooT.xml
+fn output_path(file_path: &Path, out_dir: Option<&Path>, suffix: &str) -> PathBuf {
+    let stem = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let file_name = format!("{}{}", stem, suffix);
+    match out_dir {
+        Some(dir) => dir.join(file_name),
+        None => file_path.with_file_name(file_name),
+    }
+}
+
+// Foo/ gives Foo/Foo.asm, and Main.jack gives Main.asm
+fn program_output_path(path: &Path, out_dir: Option<&Path>, suffix: &str) -> PathBuf {
+    if !path.is_dir() {
+        return output_path(path, out_dir, suffix);
+    }
+    // Canonical so that `.` is named too
+    let dir = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let name = dir.file_name().and_then(|s| s.to_str()).unwrap_or("program");
+    out_dir.unwrap_or(path).join(format!("{}{}", name, suffix))
+}
+
+fn write_output(path: &Path, content: &str) -> Result<(), Failure> {
+    fs::write(path, content)
+        .map_err(|e| Failure::Io(format!("could not write the file {}: {}", path.display(), e)))
+}
+
+// One line per file on stdout, e.g. `failed Main.jack: 2 errors`, so that scripts can tell which files broke.
+// The program's outputs, if any were asked for, get a line of their own.
+fn print_summary(outcomes: &[(&PathBuf, Outcome)], program: Option<&(&PathBuf, Outcome)>, verb: &str) {
+    println!("=== SUMMARY ===");
+    let mut failed = 0;
+    for (jack_path, outcome) in outcomes {
+        if !print_summary_line(jack_path, outcome) {
+            failed += 1;
+        }
+    }
+    if let Some((path, outcome)) = program {
+        print_summary_line(path, outcome);
+    }
+    println!("{} {}, {} failed", outcomes.len() - failed, verb, failed);
+}
+
+// Returns whether the outcome was a success
+fn print_summary_line(path: &Path, outcome: &Outcome) -> bool {
+    match outcome {
+        Ok(compiled) if compiled.written.is_empty() => println!("ok     {}", path.display()),
+        Ok(compiled) => {
+            let written: Vec<String> = compiled.written.iter().map(|path| path.display().to_string()).collect();
+            println!("ok     {} -> {}", path.display(), written.join(", "));
+        }
+        Err(failure) => {
+            let reason = match failure {
+                Failure::Compile { diagnostics, .. } => match count(diagnostics, Severity::Error) {
+                    1 => "1 error".to_string(),
+                    errors => format!("{} errors", errors),
+                },
+                Failure::Io(_) => "I/O error".to_string(),
+                Failure::Usage(_) => "usage error".to_string(),
+                Failure::Link(_) => "link error".to_string(),
+                Failure::Runtime(_) => "runtime error".to_string(),
+            };
+            println!("failed {}: {}", path.display(), reason);
+            return false;
+        }
+    }
+    true
+}
+
+// A program that fails to link fails the run but not any file, though its diagnostics count in the totals
+fn print_summary_record(outcomes: &[(&PathBuf, Outcome)], program: Option<&(&PathBuf, Outcome)>) {
+    let failed = outcomes.iter().filter(|(_, outcome)| outcome.is_err()).count();
+    let (mut errors, mut warnings) = (0, 0);
+    for (_, outcome) in outcomes.iter().chain(program) {
+        match outcome {
+            Ok(compiled) => warnings += compiled.diagnostics.len(),
+            Err(Failure::Compile { diagnostics, .. }) => {
+                errors += count(diagnostics, Severity::Error);
+                warnings += count(diagnostics, Severity::Warning);
+            }
+            Err(_) => {}
+        }
+    }
+    let success = failed == 0 && program.is_none_or(|(_, outcome)| outcome.is_ok());
+    println!("{}", json::summary_record(outcomes.len(), failed, errors, warnings, success));
+}
+
+fn count(diagnostics: &[Diagnostic], severity: Severity) -> usize {
+    diagnostics.iter().filter(|d| d.severity == severity).count()
+}
+
+// The outcomes of the files and then that of the program, whose diagnostics are about the outputs built from them
+fn write_sarif<'a>(path: &Path, outcomes: impl Iterator<Item = &'a (&'a PathBuf, Outcome)>, lints: &LintConfig) -> Result<(), Failure> {
+    let files: Vec<(&Path, &[Diagnostic])> = outcomes
+        .filter_map(|(file_path, outcome)| match outcome {
+            Ok(compiled) => Some((file_path.as_path(), compiled.diagnostics.as_slice())),
+            Err(Failure::Compile { diagnostics, .. }) => Some((file_path.as_path(), diagnostics.as_slice())),
+            Err(_) => None,
+        })
+        .collect();
+    write_output(path, &format!("{}\n", sarif::log(&files, lints)))
+}
 
-fn debug_tokens(tokens: &[Token]) {
-    println!("=== TOKENS DEBUG ===");
-    for (i, token) in tokens.iter().enumerate() {
-        println!("{}: {:?}", i, token);
+// One token per line with its position, kind and text, e.g. `3:5 keyword let`
+fn token_listing(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        let (kind, text) = match &token.token_type {
+            TokenType::Keyword(k) => ("keyword", k.as_str().to_string()),
+            TokenType::Symbol(c) => ("symbol", c.to_string()),
+            TokenType::IntConst(n) => ("integerConstant", n.to_string()),
+            TokenType::StrConst(s) => ("stringConstant", format!("\"{}\"", s)),
+            TokenType::Identifier(name) => ("identifier", name.clone()),
+            TokenType::Error => ("error", token.value.clone()),
+        };
+        out.push_str(&format!("{}:{} {} {}\n", token.span.line, token.span.column, kind, text));
     }
-    println!("==================\n");
+    out
 }
 
-fn debug_ast(ast: &ClassNode) {
-    println!("=== AST DEBUG ===");
-    println!("{:#?}", ast);
-    println!("===============\n");
+fn ast_listing(ast: &ClassNode) -> String {
+    format!("{:#?}\n", ast)
 }
@@ -0,0 +1,747 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+// The eight classes of the Jack OS, written in Rust for the emulator to call
+// when no loaded file defines them. Their data is in RAM where the OS written
+// in Jack would put it, so a program can peek and poke at it:
+//
+// RAM[2048]         the address of the first free block of the heap, 0 if there is none
+// RAM[2049..16384]  the heap, handed out in blocks by `Memory.alloc`
+// RAM[16384..24576] the screen, 32 words per row of 512 pixels, the lowest bit leftmost
+// RAM[24576]        the keyboard, the code of the key held down or 0
+//
+// A heap block starts with its size, that word included, and `Memory.alloc`
+// returns the address after it. Free blocks are listed in address order, each
+// with the address of the next one after its size.
+//
+// A String is an object of three words: the address of its characters, its
+// length and its maximum length. Output draws characters on the screen in
+// the Jack OS's font, 23 rows of 64 characters of 8 by 11 pixels, and also
+// writes them to a text console, which is where the keyboard functions other
+// than `keyPressed` read from.
+//
+// Errors carry the code the Jack OS would pass to `Sys.error`.
+
+const FREE_LIST: usize = 2048;
+const HEAP: usize = FREE_LIST + 1;
+const SCREEN: usize = 16384;
+const KEYBOARD: usize = 24576;
+const SCREEN_WIDTH: i32 = 512;
+const SCREEN_HEIGHT: i32 = 256;
+const ROWS: usize = 23;
+const COLUMNS: usize = 64;
+const CHAR_HEIGHT: usize = 11;
+const SCREEN_ROW_WORDS: usize = 32;
+
+// The word after a heap block's size, which for a free block is the address of the next one
+const NEXT: usize = 1;
+
+// Fields of a String object
+const CHARS: i16 = 0;
+const LENGTH: i16 = 1;
+const MAX_LENGTH: i16 = 2;
+
+// Characters of the Jack character set that aren't ASCII, and the quote Jack strings can't contain
+const NEW_LINE: i16 = 128;
+const BACKSPACE: i16 = 129;
+const DOUBLE_QUOTE: i16 = 34;
+
+/// What the emulator does once a native function is done.
+#[derive(Debug)]
+pub(crate) enum Effect {
+    /// Return the value, 0 for void functions
+    Return(i16),
+    /// Stop the program, for `Sys.halt`
+    Halt,
+    /// Start over from `Main.main`, for `Sys.init`
+    RunMain,
+}
+
+type Run = fn(&mut Os, &mut [i16], &[i16]) -> Result<Effect, String>;
+
+#[derive(Debug)]
+pub(crate) struct Native {
+    pub name: &'static str,
+    /// Arguments, `this` included for methods
+    pub args: u16,
+    pub run: Run,
+}
+
+const fn native(name: &'static str, args: u16, run: Run) -> Native {
+    Native { name, args, run }
+}
+
+const NATIVES: &[Native] = &[
+    native("Math.init", 0, |_, _, _| Ok(Effect::Return(0))),
+    native("Math.abs", 1, |_, _, args| Ok(Effect::Return(args[0].wrapping_abs()))),
+    native("Math.multiply", 2, |_, _, args| Ok(Effect::Return(args[0].wrapping_mul(args[1])))),
+    native("Math.divide", 2, |_, _, args| match args[1] {
+        0 => Err(os_error(3, "division by zero")),
+        divisor => Ok(Effect::Return(args[0].wrapping_div(divisor))),
+    }),
+    native("Math.min", 2, |_, _, args| Ok(Effect::Return(args[0].min(args[1])))),
+    native("Math.max", 2, |_, _, args| Ok(Effect::Return(args[0].max(args[1])))),
+    native("Math.sqrt", 1, |_, _, args| match args[0] {
+        x if x < 0 => Err(os_error(4, &format!("square root of the negative number {}", x))),
+        x => Ok(Effect::Return((x as f64).sqrt() as i16)),
+    }),
+    native("String.new", 1, |_, ram, args| Ok(Effect::Return(new_string(ram, args[0])?))),
+    native("String.dispose", 1, |_, ram, args| {
+        let chars = read(ram, args[0].wrapping_add(CHARS))?;
+        if chars != 0 {
+            deallocate(ram, chars)?;
+        }
+        deallocate(ram, args[0])?;
+        Ok(Effect::Return(0))
+    }),
+    native("String.length", 1, |_, ram, args| Ok(Effect::Return(read(ram, args[0].wrapping_add(LENGTH))?))),
+    native("String.charAt", 2, |_, ram, args| {
+        let address = char_address(ram, args[0], args[1], 15)?;
+        Ok(Effect::Return(read(ram, address)?))
+    }),
+    native("String.setCharAt", 3, |_, ram, args| {
+        let address = char_address(ram, args[0], args[1], 16)?;
+        write(ram, address, args[2])?;
+        Ok(Effect::Return(0))
+    }),
+    native("String.appendChar", 2, |_, ram, args| {
+        let (this, length) = (args[0], read(ram, args[0].wrapping_add(LENGTH))?);
+        if length >= read(ram, this.wrapping_add(MAX_LENGTH))? {
+            return Err(os_error(17, "appended a character to a full string"));
+        }
+        write(ram, read(ram, this.wrapping_add(CHARS))?.wrapping_add(length), args[1])?;
+        write(ram, this.wrapping_add(LENGTH), length + 1)?;
+        Ok(Effect::Return(this))
+    }),
+    native("String.eraseLastChar", 1, |_, ram, args| {
+        let length = read(ram, args[0].wrapping_add(LENGTH))?;
+        if length <= 0 {
+            return Err(os_error(18, "erased the last character of an empty string"));
+        }
+        write(ram, args[0].wrapping_add(LENGTH), length - 1)?;
+        Ok(Effect::Return(0))
+    }),
+    native("String.intValue", 1, |_, ram, args| Ok(Effect::Return(int_value(&string_chars(ram, args[0])?)))),
+    native("String.setInt", 2, |_, ram, args| {
+        let this = args[0];
+        let digits: Vec<i16> = args[1].to_string().bytes().map(i16::from).collect();
+        if digits.len() as i16 > read(ram, this.wrapping_add(MAX_LENGTH))? {
+            return Err(os_error(19, &format!("{} doesn't fit in the string", args[1])));
+        }
+        let chars = read(ram, this.wrapping_add(CHARS))?;
+        for (i, digit) in digits.iter().enumerate() {
+            write(ram, chars.wrapping_add(i as i16), *digit)?;
+        }
+        write(ram, this.wrapping_add(LENGTH), digits.len() as i16)?;
+        Ok(Effect::Return(0))
+    }),
+    native("String.backSpace", 0, |_, _, _| Ok(Effect::Return(BACKSPACE))),
+    native("String.doubleQuote", 0, |_, _, _| Ok(Effect::Return(DOUBLE_QUOTE))),
+    native("String.newLine", 0, |_, _, _| Ok(Effect::Return(NEW_LINE))),
+    native("Array.new", 1, |_, ram, args| match args[0] {
+        size if size <= 0 => Err(os_error(2, &format!("array of size {}, it must be positive", size))),
+        size => Ok(Effect::Return(allocate(ram, size)?)),
+    }),
+    native("Array.dispose", 1, |_, ram, args| {
+        deallocate(ram, args[0])?;
+        Ok(Effect::Return(0))
+    }),
+    native("Output.init", 0, |_, _, _| Ok(Effect::Return(0))),
+    native("Output.moveCursor", 2, |os, ram, args| {
+        let (row, column) = (args[0], args[1]);
+        if !(0..ROWS as i16).contains(&row) || !(0..COLUMNS as i16).contains(&column) {
+            return Err(os_error(20, &format!("moved the cursor to row {}, column {}, off the screen", row, column)));
+        }
+        os.move_cursor(ram, row as usize, column as usize)?;
+        Ok(Effect::Return(0))
+    }),
+    native("Output.printChar", 1, |os, ram, args| {
+        os.print_char(ram, args[0])?;
+        Ok(Effect::Return(0))
+    }),
+    native("Output.printString", 1, |os, ram, args| {
+        for c in string_chars(ram, args[0])? {
+            os.print_char(ram, c)?;
+        }
+        Ok(Effect::Return(0))
+    }),
+    native("Output.printInt", 1, |os, ram, args| {
+        for digit in args[0].to_string().bytes() {
+            os.print_char(ram, i16::from(digit))?;
+        }
+        Ok(Effect::Return(0))
+    }),
+    native("Output.println", 0, |os, ram, _| {
+        os.print_char(ram, NEW_LINE)?;
+        Ok(Effect::Return(0))
+    }),
+    native("Output.backSpace", 0, |os, ram, _| {
+        os.print_char(ram, BACKSPACE)?;
+        Ok(Effect::Return(0))
+    }),
+    native("Screen.init", 0, |_, _, _| Ok(Effect::Return(0))),
+    native("Screen.clearScreen", 0, |_, ram, _| {
+        ram[SCREEN..KEYBOARD].fill(0);
+        Ok(Effect::Return(0))
+    }),
+    native("Screen.setColor", 1, |os, _, args| {
+        os.color = args[0] != 0;
+        Ok(Effect::Return(0))
+    }),
+    native("Screen.drawPixel", 2, |os, ram, args| {
+        let (x, y) = (i32::from(args[0]), i32::from(args[1]));
+        if !on_screen(x, y) {
+            return Err(os_error(7, &format!("drew the pixel ({}, {}), off the screen", x, y)));
+        }
+        os.draw_pixel(ram, x, y);
+        Ok(Effect::Return(0))
+    }),
+    native("Screen.drawLine", 4, |os, ram, args| {
+        let [x1, y1, x2, y2] = [args[0], args[1], args[2], args[3]].map(i32::from);
+        if !on_screen(x1, y1) || !on_screen(x2, y2) {
+            return Err(os_error(8, &format!("drew a line from ({}, {}) to ({}, {}), off the screen", x1, y1, x2, y2)));
+        }
+        os.draw_line(ram, x1, y1, x2, y2);
+        Ok(Effect::Return(0))
+    }),
+    native("Screen.drawRectangle", 4, |os, ram, args| {
+        let [x1, y1, x2, y2] = [args[0], args[1], args[2], args[3]].map(i32::from);
+        if !on_screen(x1, y1) || !on_screen(x2, y2) || x1 > x2 || y1 > y2 {
+            return Err(os_error(9, &format!("drew a rectangle from ({}, {}) to ({}, {})", x1, y1, x2, y2)));
+        }
+        for y in y1..=y2 {
+            os.draw_line(ram, x1, y, x2, y);
+        }
+        Ok(Effect::Return(0))
+    }),
+    native("Screen.drawCircle", 3, |os, ram, args| {
+        let [x, y, r] = [args[0], args[1], args[2]].map(i32::from);
+        if !on_screen(x, y) {
+            return Err(os_error(12, &format!("drew a circle around ({}, {}), off the screen", x, y)));
+        }
+        if !(0..=181).contains(&r) {
+            return Err(os_error(13, &format!("drew a circle of radius {}, it must be from 0 to 181", r)));
+        }
+        // Filled a row at a time, the parts off the screen left out
+        for dy in -r..=r {
+            let half_width = f64::from(r * r - dy * dy).sqrt() as i32;
+            let row = y + dy;
+            if (0..SCREEN_HEIGHT).contains(&row) {
+                os.draw_line(ram, (x - half_width).max(0), row, (x + half_width).min(SCREEN_WIDTH - 1), row);
+            }
+        }
+        Ok(Effect::Return(0))
+    }),
+    native("Keyboard.init", 0, |_, _, _| Ok(Effect::Return(0))),
+    native("Keyboard.keyPressed", 0, |_, ram, _| Ok(Effect::Return(ram[KEYBOARD]))),
+    native("Keyboard.readChar", 0, |os, ram, _| Ok(Effect::Return(os.read_char(ram)?))),
+    native("Keyboard.readLine", 1, |os, ram, args| {
+        for c in string_chars(ram, args[0])? {
+            os.print_char(ram, c)?;
+        }
+        let line = os.read_line(ram)?;
+        let string = new_string(ram, line.len() as i16)?;
+        for (i, c) in line.iter().enumerate() {
+            write(ram, read(ram, string.wrapping_add(CHARS))?.wrapping_add(i as i16), *c)?;
+        }
+        write(ram, string.wrapping_add(LENGTH), line.len() as i16)?;
+        Ok(Effect::Return(string))
+    }),
+    native("Keyboard.readInt", 1, |os, ram, args| {
+        for c in string_chars(ram, args[0])? {
+            os.print_char(ram, c)?;
+        }
+        Ok(Effect::Return(int_value(&os.read_line(ram)?)))
+    }),
+    native("Memory.init", 0, |_, ram, _| {
+        init_heap(ram);
+        Ok(Effect::Return(0))
+    }),
+    native("Memory.peek", 1, |_, ram, args| Ok(Effect::Return(read(ram, args[0])?))),
+    native("Memory.poke", 2, |_, ram, args| {
+        write(ram, args[0], args[1])?;
+        Ok(Effect::Return(0))
+    }),
+    native("Memory.alloc", 1, |_, ram, args| match args[0] {
+        size if size <= 0 => Err(os_error(5, &format!("allocated {} words, it must be positive", size))),
+        size => Ok(Effect::Return(allocate(ram, size)?)),
+    }),
+    native("Memory.deAlloc", 1, |_, ram, args| {
+        deallocate(ram, args[0])?;
+        Ok(Effect::Return(0))
+    }),
+    native("Sys.init", 0, |_, _, _| Ok(Effect::RunMain)),
+    native("Sys.halt", 0, |_, _, _| Ok(Effect::Halt)),
+    native("Sys.error", 1, |_, _, args| Err(format!("the program failed with error code {}", args[0]))),
+    native("Sys.wait", 1, |_, _, args| match args[0] {
+        duration if duration < 0 => Err(os_error(1, &format!("waited for {} milliseconds, it must not be negative", duration))),
+        duration => {
+            std::thread::sleep(Duration::from_millis(duration as u64));
+            Ok(Effect::Return(0))
+        }
+    }),
+];
+
+/// The native implementation of `name`, e.g. `Math.multiply`.
+pub(crate) fn lookup(name: &str) -> Option<&'static Native> {
+    NATIVES.iter().find(|native| native.name == name)
+}
+
+/// Makes the whole heap one free block, as `Memory.init` does.
+pub(crate) fn init_heap(ram: &mut [i16]) {
+    ram[FREE_LIST] = HEAP as i16;
+    ram[HEAP] = (SCREEN - HEAP) as i16;
+    ram[HEAP + NEXT] = 0;
+}
+
+/// State the OS keeps outside RAM, where the Jack OS has it in static
+/// variables: the drawing color, the cursor and the console.
+pub(crate) struct Os {
+    /// Whether pixels are drawn black
+    color: bool,
+    /// The cursor, where Output draws the next character
+    row: usize,
+    column: usize,
+    /// Characters on the console's current line, which can differ from the cursor's column after `moveCursor`
+    console_column: usize,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Default for Os {
+    fn default() -> Self {
+        Os {
+            color: true,
+            row: 0,
+            column: 0,
+            console_column: 0,
+            input: Box::new(io::empty()),
+            output: Box::new(io::sink()),
+        }
+    }
+}
+
+impl fmt::Debug for Os {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Os")
+            .field("color", &self.color)
+            .field("row", &self.row)
+            .field("column", &self.column)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Os {
+    pub fn set_console(&mut self, input: Box<dyn BufRead>, output: Box<dyn Write>) {
+        self.input = input;
+        self.output = output;
+    }
+
+    fn draw_pixel(&self, ram: &mut [i16], x: i32, y: i32) {
+        let word = &mut ram[SCREEN + (y * SCREEN_WIDTH / 16 + x / 16) as usize];
+        let bit = (1u16 << (x % 16)) as i16;
+        if self.color {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    // Steps towards the end point one pixel at a time, the way the Jack OS does,
+    // so that lines come out the same
+    fn draw_line(&self, ram: &mut [i16], x1: i32, y1: i32, x2: i32, y2: i32) {
+        let (dx, dy) = ((x2 - x1).abs(), (y2 - y1).abs());
+        let (step_x, step_y) = ((x2 - x1).signum(), (y2 - y1).signum());
+        if dy == 0 {
+            for a in 0..=dx {
+                self.draw_pixel(ram, x1 + a * step_x, y1);
+            }
+            return;
+        }
+        let (mut a, mut b, mut diff) = (0, 0, 0);
+        while a <= dx && b <= dy {
+            self.draw_pixel(ram, x1 + a * step_x, y1 + b * step_y);
+            if diff < 0 {
+                a += 1;
+                diff += dy;
+            } else {
+                b += 1;
+                diff -= dx;
+            }
+        }
+    }
+
+    // Erases the character there, as the Jack OS does. The console can't move
+    // back, so moving anywhere but right starts a new line there.
+    fn move_cursor(&mut self, ram: &mut [i16], row: usize, column: usize) -> Result<(), String> {
+        if row != self.row || column < self.column {
+            self.print("\n")?;
+            self.console_column = 0;
+        }
+        self.print(&" ".repeat(column.saturating_sub(self.console_column)))?;
+        self.console_column = self.console_column.max(column);
+        self.row = row;
+        self.column = column;
+        self.draw_char(ram, i16::from(b' '));
+        Ok(())
+    }
+
+    // Draws `c` and writes it to the console, where characters outside the
+    // Jack character set are spaces
+    fn print_char(&mut self, ram: &mut [i16], c: i16) -> Result<(), String> {
+        let (row, column) = (self.row, self.column);
+        self.put_char(ram, c);
+        match c {
+            NEW_LINE => {
+                self.console_column = 0;
+                self.print("\n")
+            }
+            BACKSPACE if self.row == row && self.column < column => {
+                self.console_column = self.console_column.saturating_sub(1);
+                self.print("\u{8} \u{8}")
+            }
+            BACKSPACE => Ok(()),
+            _ => {
+                let c = u8::try_from(c).ok().filter(|c| (32..127).contains(c)).map_or(' ', char::from);
+                self.console_column += 1;
+                self.print(c.encode_utf8(&mut [0; 4]))?;
+                if self.column == 0 {
+                    self.console_column = 0;
+                    self.print("\n")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Draws `c` at the cursor and moves the cursor on, to the start of the
+    // next line after the last column and back to the top after the last row
+    fn put_char(&mut self, ram: &mut [i16], c: i16) {
+        match c {
+            NEW_LINE => {
+                self.row = (self.row + 1) % ROWS;
+                self.column = 0;
+            }
+            BACKSPACE => {
+                if self.column > 0 {
+                    self.column -= 1;
+                } else if self.row > 0 {
+                    self.row -= 1;
+                    self.column = COLUMNS - 1;
+                }
+                self.draw_char(ram, i16::from(b' '));
+            }
+            _ => {
+                self.draw_char(ram, c);
+                if self.column == COLUMNS - 1 {
+                    self.put_char(ram, NEW_LINE);
+                } else {
+                    self.column += 1;
+                }
+            }
+        }
+    }
+
+    // Each character is half a word wide, in the low byte for even columns.
+    // Characters the font doesn't have are a black square, as in the Jack OS.
+    fn draw_char(&self, ram: &mut [i16], c: i16) {
+        let glyph = usize::try_from(c - 32).ok().and_then(|i| FONT.get(i)).unwrap_or(&MISSING_GLYPH);
+        let (shift, mask) = if self.column.is_multiple_of(2) { (0, 0xff00) } else { (8, 0x00ff) };
+        let first = SCREEN + self.row * CHAR_HEIGHT * SCREEN_ROW_WORDS + self.column / 2;
+        for (i, bits) in glyph.iter().enumerate() {
+            let word = &mut ram[first + i * SCREEN_ROW_WORDS];
+            *word = (*word as u16 & mask | u16::from(*bits) << shift) as i16;
+        }
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), String> {
+        // Flushed every time, so that a prompt shows before the program reads the answer
+        self.output.write_all(text.as_bytes()).and_then(|_| self.output.flush()).map_err(|e| format!("could not write the output: {}", e))
+    }
+
+    // The next key from the console, with the end of a line as the newline
+    // key. The console shows it as it is typed, so it is only drawn.
+    fn read_char(&mut self, ram: &mut [i16]) -> Result<i16, String> {
+        let mut byte = [0];
+        let c = loop {
+            match self.input.read(&mut byte) {
+                Ok(0) => return Err("the program read past the end of the input".to_string()),
+                Ok(_) if byte[0] == b'\r' => continue,
+                Ok(_) if byte[0] == b'\n' => break NEW_LINE,
+                Ok(_) => break i16::from(byte[0]),
+                Err(e) => return Err(format!("could not read the input: {}", e)),
+            }
+        };
+        self.put_char(ram, c);
+        if c == NEW_LINE {
+            self.console_column = 0;
+        } else {
+            self.console_column += 1;
+        }
+        Ok(c)
+    }
+
+    // A line from the console without its line ending. The console shows what
+    // was typed, so unlike on the Hack computer it is only drawn.
+    fn read_line(&mut self, ram: &mut [i16]) -> Result<Vec<i16>, String> {
+        let mut line = Vec::new();
+        match self.input.read_until(b'\n', &mut line) {
+            Ok(0) => return Err("the program read past the end of the input".to_string()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("could not read the input: {}", e)),
+        }
+        while line.last().is_some_and(|byte| *byte == b'\n' || *byte == b'\r') {
+            line.pop();
+        }
+        let line: Vec<i16> = line.into_iter().map(i16::from).collect();
+        for &c in line.iter().chain([&NEW_LINE]) {
+            self.put_char(ram, c);
+        }
+        self.console_column = 0;
+        Ok(line)
+    }
+}
+
+// The first free block big enough, with what is left of it staying free
+// unless it is too small to hold a size and a next address
+fn allocate(ram: &mut [i16], size: i16) -> Result<i16, String> {
+    let size = size as usize + 1;
+    let mut blocks = free_blocks(ram)?;
+    let Some(i) = blocks.iter().position(|&(_, free)| free >= size) else {
+        return Err(os_error(6, &format!("no free block of {} words left on the heap", size - 1)));
+    };
+    let (block, free) = blocks[i];
+    let size = if free - size < 2 {
+        blocks.remove(i);
+        free
+    } else {
+        blocks[i] = (block + size, free - size);
+        size
+    };
+    ram[block] = size as i16;
+    set_free_blocks(ram, &blocks);
+    Ok((block + 1) as i16)
+}
+
+// Frees the block, merged with the free blocks on either side of it
+fn deallocate(ram: &mut [i16], address: i16) -> Result<(), String> {
+    let not_a_block = || format!("freed RAM[{}], which is not a block from `Memory.alloc`", address as u16);
+    let block = (address as u16 as usize).wrapping_sub(1);
+    if !(HEAP..SCREEN).contains(&block) {
+        return Err(not_a_block());
+    }
+    let size = ram[block] as u16 as usize;
+    let mut blocks = free_blocks(ram)?;
+    let i = blocks.partition_point(|&(free, _)| free < block);
+    let overlaps_previous = i > 0 && blocks[i - 1].0 + blocks[i - 1].1 > block;
+    let overlaps_next = blocks.get(i).is_some_and(|&(next, _)| block + size > next);
+    if size < 2 || block + size > SCREEN || overlaps_previous || overlaps_next {
+        return Err(not_a_block());
+    }
+    blocks.insert(i, (block, size));
+    if let Some(&(next, next_size)) = blocks.get(i + 1)
+        && block + size == next
+    {
+        blocks[i].1 += next_size;
+        blocks.remove(i + 1);
+    }
+    if i > 0 && blocks[i - 1].0 + blocks[i - 1].1 == block {
+        blocks[i - 1].1 += blocks[i].1;
+        blocks.remove(i);
+    }
+    set_free_blocks(ram, &blocks);
+    Ok(())
+}
+
+// The addresses and sizes of the free blocks, in address order. The list is in
+// RAM, so a program writing past the end of an array may have broken it.
+fn free_blocks(ram: &[i16]) -> Result<Vec<(usize, usize)>, String> {
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    let mut block = ram[FREE_LIST] as u16 as usize;
+    while block != 0 {
+        let previous_end = blocks.last().map_or(HEAP, |&(previous, size)| previous + size);
+        let size = ram.get(block).map_or(0, |size| *size as u16 as usize);
+        if block < previous_end || size < 2 || block + size > SCREEN {
+            return Err(format!("the heap's list of free blocks is broken at RAM[{}], the program wrote over it", block));
+        }
+        blocks.push((block, size));
+        block = ram[block + NEXT] as u16 as usize;
+    }
+    Ok(blocks)
+}
+
+fn set_free_blocks(ram: &mut [i16], blocks: &[(usize, usize)]) {
+    ram[FREE_LIST] = blocks.first().map_or(0, |&(block, _)| block as i16);
+    for (i, &(block, size)) in blocks.iter().enumerate() {
+        ram[block] = size as i16;
+        ram[block + NEXT] = blocks.get(i + 1).map_or(0, |&(next, _)| next as i16);
+    }
+}
+
+fn os_error(code: u16, message: &str) -> String {
+    format!("{} (OS error {})", message, code)
+}
+
+fn on_screen(x: i32, y: i32) -> bool {
+    (0..SCREEN_WIDTH).contains(&x) && (0..SCREEN_HEIGHT).contains(&y)
+}
+
+fn read(ram: &[i16], address: i16) -> Result<i16, String> {
+    let address = address as u16 as usize;
+    ram.get(address).copied().ok_or_else(|| format!("read from RAM[{}], which doesn't exist", address))
+}
+
+fn write(ram: &mut [i16], address: i16, value: i16) -> Result<(), String> {
+    let address = address as u16 as usize;
+    match ram.get_mut(address) {
+        Some(word) => {
+            *word = value;
+            Ok(())
+        }
+        None => Err(format!("wrote to RAM[{}], which doesn't exist", address)),
+    }
+}
+
+// An empty string with room for `max_length` characters
+fn new_string(ram: &mut [i16], max_length: i16) -> Result<i16, String> {
+    if max_length < 0 {
+        return Err(os_error(14, &format!("string of maximum length {}, it must not be negative", max_length)));
+    }
+    let string = allocate(ram, 3)?;
+    let chars = if max_length > 0 { allocate(ram, max_length)? } else { 0 };
+    write(ram, string.wrapping_add(CHARS), chars)?;
+    write(ram, string.wrapping_add(LENGTH), 0)?;
+    write(ram, string.wrapping_add(MAX_LENGTH), max_length)?;
+    Ok(string)
+}
+
+fn string_chars(ram: &[i16], string: i16) -> Result<Vec<i16>, String> {
+    let chars = read(ram, string.wrapping_add(CHARS))?;
+    let length = read(ram, string.wrapping_add(LENGTH))?;
+    (0..length.max(0)).map(|i| read(ram, chars.wrapping_add(i))).collect()
+}
+
+// The address of character `index`, which must be within the string's length
+fn char_address(ram: &[i16], string: i16, index: i16, code: u16) -> Result<i16, String> {
+    let length = read(ram, string.wrapping_add(LENGTH))?;
+    if !(0..length).contains(&index) {
+        return Err(os_error(code, &format!("index {} is out of bounds for a string of length {}", index, length)));
+    }
+    Ok(read(ram, string.wrapping_add(CHARS))?.wrapping_add(index))
+}
+
+// The number at the start of `chars`, e.g. 123 for "123abc", with an optional minus sign
+fn int_value(chars: &[i16]) -> i16 {
+    let (negative, digits) = match chars.first() {
+        Some(&c) if c == i16::from(b'-') => (true, &chars[1..]),
+        _ => (false, chars),
+    };
+    let value = digits
+        .iter()
+        .map_while(|&c| u8::try_from(c).ok().filter(u8::is_ascii_digit))
+        .fold(0i16, |value, digit| value.wrapping_mul(10).wrapping_add(i16::from(digit - b'0')));
+    if negative { value.wrapping_neg() } else { value }
+}
+
+// Rows of the Jack OS's font for the characters from ` ` to `~`, the top row
+// first and the lowest bit leftmost
+const FONT: [[u8; CHAR_HEIGHT]; 95] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    [12, 30, 30, 30, 12, 12, 0, 12, 12, 0, 0],
+    [54, 54, 20, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 18, 18, 63, 18, 18, 63, 18, 18, 0, 0],
+    [12, 30, 51, 3, 30, 48, 51, 30, 12, 12, 0],
+    [0, 0, 35, 51, 24, 12, 6, 51, 49, 0, 0],
+    [12, 30, 30, 12, 54, 27, 27, 27, 54, 0, 0],
+    [12, 12, 6, 0, 0, 0, 0, 0, 0, 0, 0],
+    [24, 12, 6, 6, 6, 6, 6, 12, 24, 0, 0],
+    [6, 12, 24, 24, 24, 24, 24, 12, 6, 0, 0],
+    [0, 0, 0, 51, 30, 63, 30, 51, 0, 0, 0],
+    [0, 0, 0, 12, 12, 63, 12, 12, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 12, 12, 6, 0],
+    [0, 0, 0, 0, 0, 63, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 12, 12, 0, 0],
+    [0, 0, 32, 48, 24, 12, 6, 3, 1, 0, 0],
+    [12, 30, 51, 51, 51, 51, 51, 30, 12, 0, 0],
+    [12, 14, 15, 12, 12, 12, 12, 12, 63, 0, 0],
+    [30, 51, 48, 24, 12, 6, 3, 51, 63, 0, 0],
+    [30, 51, 48, 48, 28, 48, 48, 51, 30, 0, 0],
+    [16, 24, 28, 26, 25, 63, 24, 24, 60, 0, 0],
+    [63, 3, 3, 31, 48, 48, 48, 51, 30, 0, 0],
+    [28, 6, 3, 3, 31, 51, 51, 51, 30, 0, 0],
+    [63, 49, 48, 48, 24, 12, 12, 12, 12, 0, 0],
+    [30, 51, 51, 51, 30, 51, 51, 51, 30, 0, 0],
+    [30, 51, 51, 51, 62, 48, 48, 24, 14, 0, 0],
+    [0, 0, 12, 12, 0, 0, 12, 12, 0, 0, 0],
+    [0, 0, 12, 12, 0, 0, 12, 12, 6, 0, 0],
+    [0, 0, 24, 12, 6, 3, 6, 12, 24, 0, 0],
+    [0, 0, 0, 63, 0, 0, 63, 0, 0, 0, 0],
+    [0, 0, 3, 6, 12, 24, 12, 6, 3, 0, 0],
+    [30, 51, 51, 24, 12, 12, 0, 12, 12, 0, 0],
+    [30, 51, 51, 59, 59, 59, 27, 3, 30, 0, 0],
+    [12, 30, 51, 51, 63, 51, 51, 51, 51, 0, 0],
+    [31, 51, 51, 51, 31, 51, 51, 51, 31, 0, 0],
+    [28, 54, 35, 3, 3, 3, 35, 54, 28, 0, 0],
+    [15, 27, 51, 51, 51, 51, 51, 27, 15, 0, 0],
+    [63, 51, 35, 11, 15, 11, 35, 51, 63, 0, 0],
+    [63, 51, 35, 11, 15, 11, 3, 3, 3, 0, 0],
+    [28, 54, 35, 3, 59, 51, 51, 54, 44, 0, 0],
+    [51, 51, 51, 51, 63, 51, 51, 51, 51, 0, 0],
+    [30, 12, 12, 12, 12, 12, 12, 12, 30, 0, 0],
+    [60, 24, 24, 24, 24, 24, 27, 27, 14, 0, 0],
+    [51, 51, 51, 27, 15, 27, 51, 51, 51, 0, 0],
+    [3, 3, 3, 3, 3, 3, 35, 51, 63, 0, 0],
+    [33, 51, 63, 63, 51, 51, 51, 51, 51, 0, 0],
+    [51, 51, 55, 55, 63, 59, 59, 51, 51, 0, 0],
+    [30, 51, 51, 51, 51, 51, 51, 51, 30, 0, 0],
+    [31, 51, 51, 51, 31, 3, 3, 3, 3, 0, 0],
+    [30, 51, 51, 51, 51, 51, 63, 59, 30, 48, 0],
+    [31, 51, 51, 51, 31, 27, 51, 51, 51, 0, 0],
+    [30, 51, 51, 6, 28, 48, 51, 51, 30, 0, 0],
+    [63, 63, 45, 12, 12, 12, 12, 12, 30, 0, 0],
+    [51, 51, 51, 51, 51, 51, 51, 51, 30, 0, 0],
+    [51, 51, 51, 51, 51, 30, 30, 12, 12, 0, 0],
+    [51, 51, 51, 51, 51, 63, 63, 63, 18, 0, 0],
+    [51, 51, 30, 30, 12, 30, 30, 51, 51, 0, 0],
+    [51, 51, 51, 51, 30, 12, 12, 12, 30, 0, 0],
+    [63, 51, 49, 24, 12, 6, 35, 51, 63, 0, 0],
+    [30, 6, 6, 6, 6, 6, 6, 6, 30, 0, 0],
+    [0, 0, 1, 3, 6, 12, 24, 48, 32, 0, 0],
+    [30, 24, 24, 24, 24, 24, 24, 24, 30, 0, 0],
+    [8, 28, 54, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 63, 0],
+    [6, 12, 24, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 14, 24, 30, 27, 27, 54, 0, 0],
+    [3, 3, 3, 15, 27, 51, 51, 51, 30, 0, 0],
+    [0, 0, 0, 30, 51, 3, 3, 51, 30, 0, 0],
+    [48, 48, 48, 60, 54, 51, 51, 51, 30, 0, 0],
+    [0, 0, 0, 30, 51, 63, 3, 51, 30, 0, 0],
+    [28, 54, 38, 6, 15, 6, 6, 6, 15, 0, 0],
+    [0, 0, 30, 51, 51, 51, 62, 48, 51, 30, 0],
+    [3, 3, 3, 27, 55, 51, 51, 51, 51, 0, 0],
+    [12, 12, 0, 14, 12, 12, 12, 12, 30, 0, 0],
+    [48, 48, 0, 56, 48, 48, 48, 48, 51, 30, 0],
+    [3, 3, 3, 51, 27, 15, 15, 27, 51, 0, 0],
+    [14, 12, 12, 12, 12, 12, 12, 12, 30, 0, 0],
+    [0, 0, 0, 29, 63, 43, 43, 43, 43, 0, 0],
+    [0, 0, 0, 29, 51, 51, 51, 51, 51, 0, 0],
+    [0, 0, 0, 30, 51, 51, 51, 51, 30, 0, 0],
+    [0, 0, 0, 30, 51, 51, 51, 31, 3, 3, 0],
+    [0, 0, 0, 30, 51, 51, 51, 62, 48, 48, 0],
+    [0, 0, 0, 29, 55, 51, 3, 3, 7, 0, 0],
+    [0, 0, 0, 30, 51, 6, 24, 51, 30, 0, 0],
+    [4, 6, 6, 15, 6, 6, 6, 54, 28, 0, 0],
+    [0, 0, 0, 27, 27, 27, 27, 27, 54, 0, 0],
+    [0, 0, 0, 51, 51, 51, 51, 30, 12, 0, 0],
+    [0, 0, 0, 51, 51, 51, 63, 63, 18, 0, 0],
+    [0, 0, 0, 51, 30, 12, 12, 30, 51, 0, 0],
+    [0, 0, 0, 51, 51, 51, 62, 48, 24, 15, 0],
+    [0, 0, 0, 63, 27, 12, 6, 51, 63, 0, 0],
+    [56, 12, 12, 12, 7, 12, 12, 12, 56, 0, 0],
+    [12, 12, 12, 12, 12, 12, 12, 12, 12, 0, 0],
+    [7, 12, 12, 12, 56, 12, 12, 12, 7, 0, 0],
+    [38, 45, 25, 0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+const MISSING_GLYPH: [u8; CHAR_HEIGHT] = [0, 63, 63, 63, 63, 63, 63, 63, 63, 63, 0];
@@ -1,11 +1,14 @@
-use crate::tokenizer::{Token, TokenType, Keyword};
-use std::fmt::Debug;
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::Span;
+use crate::tokenizer::{Token, TokenType, Keyword, MAX_INT_CONST};
+use std::fmt::{self, Debug};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ClassNode {
     pub name: String,
     pub var_decs: Vec<ClassVarDecNode>,
     pub subroutine_decs: Vec<SubroutineDecNode>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -13,6 +16,7 @@ pub struct ClassVarDecNode {
     pub kind: ClassVarKind,
     pub var_type: Type,
     pub names: Vec<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -29,13 +33,33 @@ pub enum Type {
     ClassName(String),
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Char => write!(f, "char"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::ClassName(name) => write!(f, "{}", name),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct SubroutineDecNode {
     pub kind: SubroutineKind,
     pub return_type: Option<Type>, // None for void
     pub name: String,
-    pub parameters: Vec<(Type, String)>,
+    pub parameters: Vec<ParameterNode>,
     pub body: SubroutineBodyNode,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParameterNode {
+    pub var_type: Type,
+    pub name: String,
+    /// The type and the name
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -55,6 +79,7 @@ pub struct SubroutineBodyNode {
 pub struct VarDecNode {
     pub var_type: Type,
     pub names: Vec<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -66,11 +91,25 @@ pub enum StatementNode {
     Return(ReturnStatementNode),
 }
 
+impl StatementNode {
+    pub fn span(&self) -> Span {
+        match self {
+            StatementNode::Let(stmt) => stmt.span,
+            StatementNode::If(stmt) => stmt.span,
+            StatementNode::While(stmt) => stmt.span,
+            StatementNode::Do(stmt) => stmt.span,
+            StatementNode::Return(stmt) => stmt.span,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct LetStatementNode {
     pub var_name: String,
+    pub name_span: Span,
     pub index_expr: Option<Box<ExpressionNode>>,
     pub value_expr: Box<ExpressionNode>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -78,32 +117,43 @@ pub struct IfStatementNode {
     pub condition: Box<ExpressionNode>,
     pub if_block: Vec<StatementNode>,
     pub else_block: Option<Vec<StatementNode>>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct WhileStatementNode {
     pub condition: Box<ExpressionNode>,
     pub body: Vec<StatementNode>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct DoStatementNode {
     pub call: SubroutineCallNode,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ReturnStatementNode {
     pub value: Option<Box<ExpressionNode>>,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ExpressionNode {
     pub initial_term: Box<TermNode>,
     pub operations: Vec<(char, Box<TermNode>)>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TermNode {
+    pub kind: TermKind,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum TermNode {
+pub enum TermKind {
     IntConst(u16),
     StrConst(String),
     KeywordConst(Keyword),
@@ -117,43 +167,108 @@ pub enum TermNode {
 #[derive(Debug, PartialEq, Clone)]
 pub struct SubroutineCallNode {
     pub receiver: Option<String>,
+    pub receiver_span: Option<Span>,
     pub name: String,
+    pub name_span: Span,
     pub args: Vec<ExpressionNode>,
+    pub span: Span,
 }
 
 
+const CLASS_VAR_KEYWORDS: [Keyword; 2] = [Keyword::Static, Keyword::Field];
+const SUBROUTINE_KEYWORDS: [Keyword; 3] = [Keyword::Constructor, Keyword::Function, Keyword::Method];
+const STATEMENT_KEYWORDS: [Keyword; 5] = [Keyword::Let, Keyword::If, Keyword::While, Keyword::Do, Keyword::Return];
+
 // Parser Implementation
 pub struct Parser<'a> {
     tokens: &'a [Token],
     position: usize,
+    errors: Vec<Diagnostic>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
-        Parser { tokens, position: 0 }
-    }
-
-    pub fn parse_class(&mut self) -> Result<ClassNode, String> {
-        self.expect_keyword(Keyword::Class)?;
-        let name = self.expect_identifier()?;
-        self.expect_symbol('{')?;
+        Parser { tokens, position: 0, errors: Vec::new() }
+    }
+
+    /// Parses the whole class, recovering from syntax errors so that every
+    /// error in the file is reported. Declarations and statements that failed
+    /// to parse are left out of the returned class.
+    pub fn parse_class(&mut self) -> (ClassNode, Vec<Diagnostic>) {
+        let start = self.current_span();
+        let name = match self.parse_class_header() {
+            Ok(name) => name,
+            Err(e) => {
+                self.report(*e);
+                self.synchronize_member();
+                String::new()
+            }
+        };
 
         let mut var_decs = Vec::new();
-        while self.peek_keyword(&[Keyword::Static, Keyword::Field]) {
-            var_decs.push(self.parse_class_var_dec()?);
+        let mut subroutine_decs = Vec::new();
+        loop {
+            while self.peek_keyword(&CLASS_VAR_KEYWORDS) {
+                if !subroutine_decs.is_empty() {
+                    let error = Diagnostic::error(
+                        codes::UNEXPECTED_TOKEN,
+                        "class variables must be declared before any subroutine",
+                        self.current_span(),
+                    );
+                    self.report(error);
+                }
+                match self.parse_class_var_dec() {
+                    Ok(var_dec) => var_decs.push(var_dec),
+                    Err(e) => {
+                        self.report(*e);
+                        self.synchronize_member();
+                    }
+                }
+            }
+
+            while self.peek_keyword(&SUBROUTINE_KEYWORDS) {
+                match self.parse_subroutine_dec() {
+                    Ok(subroutine) => subroutine_decs.push(subroutine),
+                    Err(e) => {
+                        self.report(*e);
+                        self.synchronize_member();
+                    }
+                }
+            }
+
+            if self.peek().is_none() || self.peek_symbol('}') {
+                break;
+            }
+            let mut expected: Vec<String> = CLASS_VAR_KEYWORDS.iter().chain(&SUBROUTINE_KEYWORDS)
+                .map(|k| format!("`{}`", k.as_str()))
+                .collect();
+            expected.push("`}`".to_string());
+            let error = self.unexpected(expected);
+            self.report(*error);
+            self.advance();
+            self.synchronize_member();
         }
 
-        let mut subroutine_decs = Vec::new();
-        while self.peek_keyword(&[Keyword::Constructor, Keyword::Function, Keyword::Method]) {
-            subroutine_decs.push(self.parse_subroutine_dec()?);
+        if let Err(e) = self.expect_symbol('}') {
+            self.report(*e);
+        } else if self.peek().is_some() {
+            let error = self.unexpected(vec!["end of file".to_string()]);
+            self.report(*error);
         }
 
-        self.expect_symbol('}')?;
+        let class = ClassNode { name, var_decs, subroutine_decs, span: start.to(self.previous_span()) };
+        (class, std::mem::take(&mut self.errors))
+    }
 
-        Ok(ClassNode { name, var_decs, subroutine_decs })
+    fn parse_class_header(&mut self) -> Result<String, Box<Diagnostic>> {
+        self.expect_keyword(Keyword::Class)?;
+        let name = self.expect_identifier()?;
+        self.expect_symbol('{')?;
+        Ok(name)
     }
 
-    fn parse_class_var_dec(&mut self) -> Result<ClassVarDecNode, String> {
+    fn parse_class_var_dec(&mut self) -> Result<ClassVarDecNode, Box<Diagnostic>> {
+        let start = self.current_span();
         let kind = match self.expect_one_of_keywords(&[Keyword::Static, Keyword::Field])? {
             Keyword::Static => ClassVarKind::Static,
             Keyword::Field => ClassVarKind::Field,
@@ -167,30 +282,31 @@ impl<'a> Parser<'a> {
         }
         self.expect_symbol(';')?;
 
-        Ok(ClassVarDecNode { kind, var_type, names })
+        Ok(ClassVarDecNode { kind, var_type, names, span: start.to(self.previous_span()) })
     }
 
-    fn parse_type(&mut self) -> Result<Type, String> {
+    fn parse_type(&mut self) -> Result<Type, Box<Diagnostic>> {
         if let Some(token) = self.peek() {
             match &token.token_type {
                 TokenType::Keyword(k) => match k {
                     Keyword::Int => { self.advance(); Ok(Type::Int) },
                     Keyword::Char => { self.advance(); Ok(Type::Char) },
                     Keyword::Boolean => { self.advance(); Ok(Type::Boolean) },
-                    _ => Err(format!("Expected type keyword, found {:?}", k))
+                    _ => Err(self.unexpected(vec!["type".to_string()])),
                 },
                 TokenType::Identifier(_) => {
                     let name = self.expect_identifier()?;
                     Ok(Type::ClassName(name))
                 },
-                _ => Err(format!("Expected type, found {:?}", token)),
+                _ => Err(self.unexpected(vec!["type".to_string()])),
             }
         } else {
-            Err("Expected type, found EOF".to_string())
+            Err(self.unexpected(vec!["type".to_string()]))
         }
     }
 
-    fn parse_subroutine_dec(&mut self) -> Result<SubroutineDecNode, String> {
+    fn parse_subroutine_dec(&mut self) -> Result<SubroutineDecNode, Box<Diagnostic>> {
+        let start = self.current_span();
         let kind = match self.expect_one_of_keywords(&[Keyword::Constructor, Keyword::Function, Keyword::Method])? {
             Keyword::Constructor => SubroutineKind::Constructor,
             Keyword::Function => SubroutineKind::Function,
@@ -210,37 +326,49 @@ impl<'a> Parser<'a> {
         self.expect_symbol(')')?;
         let body = self.parse_subroutine_body()?;
 
-        Ok(SubroutineDecNode { kind, return_type, name, parameters, body })
+        Ok(SubroutineDecNode { kind, return_type, name, parameters, body, span: start.to(self.previous_span()) })
     }
 
-    fn parse_parameter_list(&mut self) -> Result<Vec<(Type, String)>, String> {
+    fn parse_parameter_list(&mut self) -> Result<Vec<ParameterNode>, Box<Diagnostic>> {
         let mut params = Vec::new();
         if !self.peek_symbol(')') {
-            let p_type = self.parse_type()?;
-            let p_name = self.expect_identifier()?;
-            params.push((p_type, p_name));
-
+            params.push(self.parse_parameter()?);
             while self.match_symbol(',') {
-                let p_type = self.parse_type()?;
-                let p_name = self.expect_identifier()?;
-                params.push((p_type, p_name));
+                params.push(self.parse_parameter()?);
             }
         }
         Ok(params)
     }
 
-    fn parse_subroutine_body(&mut self) -> Result<SubroutineBodyNode, String> {
+    fn parse_parameter(&mut self) -> Result<ParameterNode, Box<Diagnostic>> {
+        let start = self.current_span();
+        let var_type = self.parse_type()?;
+        let name = self.expect_identifier()?;
+        Ok(ParameterNode { var_type, name, span: start.to(self.previous_span()) })
+    }
+
+    fn parse_subroutine_body(&mut self) -> Result<SubroutineBodyNode, Box<Diagnostic>> {
         self.expect_symbol('{')?;
         let mut var_decs = Vec::new();
         while self.peek_keyword(&[Keyword::Var]) {
-            var_decs.push(self.parse_var_dec()?);
+            match self.parse_var_dec() {
+                Ok(var_dec) => var_decs.push(var_dec),
+                Err(e) => {
+                    self.report(*e);
+                    self.synchronize_statement();
+                }
+            }
+        }
+        let statements = self.parse_statements();
+        // Keep the subroutine even if its closing brace is missing
+        if let Err(e) = self.expect_symbol('}') {
+            self.report(*e);
         }
-        let statements = self.parse_statements()?;
-        self.expect_symbol('}')?;
         Ok(SubroutineBodyNode { var_decs, statements })
     }
 
-    fn parse_var_dec(&mut self) -> Result<VarDecNode, String> {
+    fn parse_var_dec(&mut self) -> Result<VarDecNode, Box<Diagnostic>> {
+        let start = self.current_span();
         self.expect_keyword(Keyword::Var)?;
         let var_type = self.parse_type()?;
         let mut names = vec![self.expect_identifier()?];
@@ -249,39 +377,58 @@ impl<'a> Parser<'a> {
             names.push(self.expect_identifier()?);
         }
         self.expect_symbol(';')?;
-        Ok(VarDecNode { var_type, names })
+        Ok(VarDecNode { var_type, names, span: start.to(self.previous_span()) })
     }
 
-    fn parse_statements(&mut self) -> Result<Vec<StatementNode>, String> {
+    // Parses statements up to the closing `}` of the enclosing block. Statements
+    // with errors are reported and skipped.
+    fn parse_statements(&mut self) -> Vec<StatementNode> {
         let mut statements = Vec::new();
-        while self.is_statement() {
-            statements.push(self.parse_statement()?);
+        loop {
+            if self.is_statement() {
+                match self.parse_statement() {
+                    Ok(statement) => statements.push(statement),
+                    Err(e) => {
+                        self.report(*e);
+                        self.synchronize_statement();
+                    }
+                }
+            } else if self.peek().is_none() || self.peek_symbol('}') || self.peek_keyword(&SUBROUTINE_KEYWORDS) {
+                break;
+            } else {
+                let error = self.unexpected(vec!["statement".to_string()]);
+                self.report(*error);
+                self.advance();
+                self.synchronize_statement();
+            }
         }
-        Ok(statements)
+        statements
     }
 
     fn is_statement(&self) -> bool {
-        self.peek_keyword(&[Keyword::Let, Keyword::If, Keyword::While, Keyword::Do, Keyword::Return])
-    }
-
-    fn parse_statement(&mut self) -> Result<StatementNode, String> {
-        if let Some(token) = self.peek() {
-            if let TokenType::Keyword(k) = &token.token_type {
-                return match k {
-                    Keyword::Let => Ok(StatementNode::Let(self.parse_let_statement()?)),
-                    Keyword::If => Ok(StatementNode::If(self.parse_if_statement()?)),
-                    Keyword::While => Ok(StatementNode::While(self.parse_while_statement()?)),
-                    Keyword::Do => Ok(StatementNode::Do(self.parse_do_statement()?)),
-                    Keyword::Return => Ok(StatementNode::Return(self.parse_return_statement()?)),
-                    _ => Err(format!("Expected statement keyword, found {:?}", k)),
-                };
-            }
+        self.peek_keyword(&STATEMENT_KEYWORDS)
+    }
+
+    fn parse_statement(&mut self) -> Result<StatementNode, Box<Diagnostic>> {
+        if let Some(token) = self.peek()
+            && let TokenType::Keyword(k) = &token.token_type
+        {
+            return match k {
+                Keyword::Let => Ok(StatementNode::Let(self.parse_let_statement()?)),
+                Keyword::If => Ok(StatementNode::If(self.parse_if_statement()?)),
+                Keyword::While => Ok(StatementNode::While(self.parse_while_statement()?)),
+                Keyword::Do => Ok(StatementNode::Do(self.parse_do_statement()?)),
+                Keyword::Return => Ok(StatementNode::Return(self.parse_return_statement()?)),
+                _ => Err(self.unexpected(vec!["statement".to_string()])),
+            };
         }
-        Err("Expected statement, but found nothing or not a keyword".to_string())
+        Err(self.unexpected(vec!["statement".to_string()]))
     }
 
-    fn parse_let_statement(&mut self) -> Result<LetStatementNode, String> {
+    fn parse_let_statement(&mut self) -> Result<LetStatementNode, Box<Diagnostic>> {
+        let start = self.current_span();
         self.expect_keyword(Keyword::Let)?;
+        let name_span = self.current_span();
         let var_name = self.expect_identifier()?;
         let mut index_expr = None;
         if self.match_symbol('[') {
@@ -291,46 +438,50 @@ impl<'a> Parser<'a> {
         self.expect_symbol('=')?;
         let value_expr = Box::new(self.parse_expression()?);
         self.expect_symbol(';')?;
-        Ok(LetStatementNode { var_name, index_expr, value_expr })
+        Ok(LetStatementNode { var_name, name_span, index_expr, value_expr, span: start.to(self.previous_span()) })
     }
 
-    fn parse_if_statement(&mut self) -> Result<IfStatementNode, String> {
+    fn parse_if_statement(&mut self) -> Result<IfStatementNode, Box<Diagnostic>> {
+        let start = self.current_span();
         self.expect_keyword(Keyword::If)?;
         self.expect_symbol('(')?;
         let condition = Box::new(self.parse_expression()?);
         self.expect_symbol(')')?;
         self.expect_symbol('{')?;
-        let if_block = self.parse_statements()?;
+        let if_block = self.parse_statements();
         self.expect_symbol('}')?;
 
         let mut else_block = None;
         if self.match_keyword(Keyword::Else) {
             self.expect_symbol('{')?;
-            else_block = Some(self.parse_statements()?);
+            else_block = Some(self.parse_statements());
             self.expect_symbol('}')?;
         }
-        Ok(IfStatementNode { condition, if_block, else_block })
+        Ok(IfStatementNode { condition, if_block, else_block, span: start.to(self.previous_span()) })
     }
 
-    fn parse_while_statement(&mut self) -> Result<WhileStatementNode, String> {
+    fn parse_while_statement(&mut self) -> Result<WhileStatementNode, Box<Diagnostic>> {
+        let start = self.current_span();
         self.expect_keyword(Keyword::While)?;
         self.expect_symbol('(')?;
         let condition = Box::new(self.parse_expression()?);
         self.expect_symbol(')')?;
         self.expect_symbol('{')?;
-        let body = self.parse_statements()?;
+        let body = self.parse_statements();
         self.expect_symbol('}')?;
-        Ok(WhileStatementNode { condition, body })
+        Ok(WhileStatementNode { condition, body, span: start.to(self.previous_span()) })
     }
 
-    fn parse_do_statement(&mut self) -> Result<DoStatementNode, String> {
+    fn parse_do_statement(&mut self) -> Result<DoStatementNode, Box<Diagnostic>> {
+        let start = self.current_span();
         self.expect_keyword(Keyword::Do)?;
         let call = self.parse_subroutine_call()?;
         self.expect_symbol(';')?;
-        Ok(DoStatementNode { call })
+        Ok(DoStatementNode { call, span: start.to(self.previous_span()) })
     }
 
-    fn parse_return_statement(&mut self) -> Result<ReturnStatementNode, String> {
+    fn parse_return_statement(&mut self) -> Result<ReturnStatementNode, Box<Diagnostic>> {
+        let start = self.current_span();
         self.expect_keyword(Keyword::Return)?;
         let value = if !self.peek_symbol(';') {
             Some(Box::new(self.parse_expression()?))
@@ -338,10 +489,11 @@ impl<'a> Parser<'a> {
             None
         };
         self.expect_symbol(';')?;
-        Ok(ReturnStatementNode { value })
+        Ok(ReturnStatementNode { value, span: start.to(self.previous_span()) })
     }
 
-    fn parse_expression(&mut self) -> Result<ExpressionNode, String> {
+    fn parse_expression(&mut self) -> Result<ExpressionNode, Box<Diagnostic>> {
+        let start = self.current_span();
         let initial_term = Box::new(self.parse_term()?);
         let mut operations = Vec::new();
         while let Some(op) = self.peek_op() {
@@ -349,73 +501,99 @@ impl<'a> Parser<'a> {
             let term = Box::new(self.parse_term()?);
             operations.push((op, term));
         }
-        Ok(ExpressionNode { initial_term, operations })
+        Ok(ExpressionNode { initial_term, operations, span: start.to(self.previous_span()) })
     }
 
-    fn parse_term(&mut self) -> Result<TermNode, String> {
+    fn parse_term(&mut self) -> Result<TermNode, Box<Diagnostic>> {
+        let start = self.current_span();
+        let kind = self.parse_term_kind()?;
+        Ok(TermNode { kind, span: start.to(self.previous_span()) })
+    }
+
+    fn parse_term_kind(&mut self) -> Result<TermKind, Box<Diagnostic>> {
         if let Some(token) = self.peek() {
             return match token.token_type.clone() {
                 TokenType::IntConst(val) => {
+                    // 32768 only lexes so that `-32768` can be written, see the unary minus case
+                    if val > MAX_INT_CONST {
+                        let error = Diagnostic::error(
+                            codes::INVALID_INTEGER,
+                            format!("integer constant `{}` is out of range", val),
+                            token.span,
+                        ).with_note(format!("integer constants must be in the range 0..{}, `-{}` is only accepted as a whole", MAX_INT_CONST, val));
+                        self.report(error);
+                    }
                     self.advance();
-                    Ok(TermNode::IntConst(val))
+                    Ok(TermKind::IntConst(val))
                 },
                 TokenType::StrConst(s) => {
                     self.advance();
-                    Ok(TermNode::StrConst(s))
+                    Ok(TermKind::StrConst(s))
                 },
                 TokenType::Keyword(k @ Keyword::True) |
                 TokenType::Keyword(k @ Keyword::False) |
                 TokenType::Keyword(k @ Keyword::Null) |
                 TokenType::Keyword(k @ Keyword::This) => {
                     self.advance();
-                    Ok(TermNode::KeywordConst(k))
+                    Ok(TermKind::KeywordConst(k))
                 },
                 TokenType::Identifier(_) => {
                     if self.peek_next_symbol('.') || self.peek_next_symbol('(') {
-                        Ok(TermNode::SubroutineCall(self.parse_subroutine_call()?))
+                        Ok(TermKind::SubroutineCall(self.parse_subroutine_call()?))
                     } else if self.peek_next_symbol('[') {
                         let name = self.expect_identifier()?;
                         self.expect_symbol('[')?;
                         let expr = self.parse_expression()?;
                         self.expect_symbol(']')?;
-                        Ok(TermNode::ArrayAccess(name, Box::new(expr)))
+                        Ok(TermKind::ArrayAccess(name, Box::new(expr)))
                     } else {
-                        Ok(TermNode::VarName(self.expect_identifier()?))
+                        Ok(TermKind::VarName(self.expect_identifier()?))
                     }
                 },
                 TokenType::Symbol('(') => {
                     self.advance();
                     let expr = self.parse_expression()?;
                     self.expect_symbol(')')?;
-                    Ok(TermNode::Parenthesized(Box::new(expr)))
+                    Ok(TermKind::Parenthesized(Box::new(expr)))
                 },
                 TokenType::Symbol(op @ '-') | TokenType::Symbol(op @ '~') => {
                     self.advance();
+                    // `-32768` is the one place the magnitude may exceed MAX_INT_CONST
+                    if op == '-'
+                        && let Some(next) = self.peek()
+                        && next.token_type == TokenType::IntConst(MAX_INT_CONST + 1)
+                    {
+                        let term = TermNode { kind: TermKind::IntConst(MAX_INT_CONST + 1), span: next.span };
+                        self.advance();
+                        return Ok(TermKind::UnaryOp(op, Box::new(term)));
+                    }
                     let term = self.parse_term()?;
-                    Ok(TermNode::UnaryOp(op, Box::new(term)))
+                    Ok(TermKind::UnaryOp(op, Box::new(term)))
                 }
-                _ => Err(format!("Unexpected token in term: {:?}", token))
+                _ => Err(self.unexpected(vec!["term".to_string()])),
             }
         }
-        Err("Unexpected EOF in term".to_string())
+        Err(self.unexpected(vec!["term".to_string()]))
     }
 
-    fn parse_subroutine_call(&mut self) -> Result<SubroutineCallNode, String> {
+    fn parse_subroutine_call(&mut self) -> Result<SubroutineCallNode, Box<Diagnostic>> {
+        let start = self.current_span();
         let first_identifier = self.expect_identifier()?;
-        let (receiver, name) = if self.match_symbol('.') {
-            (Some(first_identifier), self.expect_identifier()?)
+        let (receiver, receiver_span, name, name_span) = if self.match_symbol('.') {
+            let name_span = self.current_span();
+            (Some(first_identifier), Some(start), self.expect_identifier()?, name_span)
         } else {
-            (None, first_identifier)
+            (None, None, first_identifier, start)
         };
 
         self.expect_symbol('(')?;
         let args = self.parse_expression_list()?;
         self.expect_symbol(')')?;
 
-        Ok(SubroutineCallNode { receiver, name, args })
+        Ok(SubroutineCallNode { receiver, receiver_span, name, name_span, args, span: start.to(self.previous_span()) })
     }
 
-    fn parse_expression_list(&mut self) -> Result<Vec<ExpressionNode>, String> {
+    fn parse_expression_list(&mut self) -> Result<Vec<ExpressionNode>, Box<Diagnostic>> {
         let mut expressions = Vec::new();
         if !self.peek_symbol(')') {
             expressions.push(self.parse_expression()?);
@@ -426,11 +604,111 @@ impl<'a> Parser<'a> {
         Ok(expressions)
     }
 
+    // Error recovery
+
+    // Records a syntax error, unless it was caused by a token the tokenizer already
+    // reported. A file that ends early is reported once, not for every open block.
+    fn report(&mut self, error: Diagnostic) {
+        if self.peek().is_some_and(|token| token.token_type == TokenType::Error) {
+            return;
+        }
+        if error.code == codes::UNEXPECTED_EOF && self.errors.iter().any(|e| e.code == codes::UNEXPECTED_EOF) {
+            return;
+        }
+        self.errors.push(error);
+    }
+
+    // Skips to the start of the next statement: just past a `;`, or before a
+    // statement keyword or the `}` closing the current block. Nested blocks are
+    // skipped whole.
+    fn synchronize_statement(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match &token.token_type {
+                TokenType::Symbol(';') if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::Symbol('{') => depth += 1,
+                TokenType::Symbol('}') => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                }
+                TokenType::Keyword(k) if depth == 0 && STATEMENT_KEYWORDS.contains(k) => return,
+                TokenType::Keyword(k) if SUBROUTINE_KEYWORDS.contains(k) => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    // Skips to the next class member declaration or the `}` closing the class.
+    fn synchronize_member(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match &token.token_type {
+                TokenType::Symbol(';') if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::Symbol('{') => depth += 1,
+                TokenType::Symbol('}') => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                }
+                TokenType::Keyword(k) if CLASS_VAR_KEYWORDS.contains(k) || SUBROUTINE_KEYWORDS.contains(k) => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
     // Utility functions
     fn peek(&self) -> Option<&Token> {
         self.tokens.get(self.position)
     }
 
+    // Error for the next token not being any of `expected`
+    fn unexpected(&self, expected: Vec<String>) -> Box<Diagnostic> {
+        let wanted = match expected.as_slice() {
+            [single] => single.clone(),
+            _ => format!("one of {} possible tokens", expected.len()),
+        };
+        let diagnostic = match self.peek() {
+            Some(token) => Diagnostic::error(
+                codes::UNEXPECTED_TOKEN,
+                format!("expected {}, found {}", wanted, token.describe()),
+                token.span,
+            ),
+            None => Diagnostic::error(
+                codes::UNEXPECTED_EOF,
+                format!("expected {}, found end of file", wanted),
+                self.current_span(),
+            ),
+        };
+        Box::new(diagnostic.with_expected(expected))
+    }
+
+    // Span of the next token, or an empty span at the end of input
+    fn current_span(&self) -> Span {
+        match self.peek() {
+            Some(token) => token.span,
+            None => self.previous_span().after(),
+        }
+    }
+
+    // Span of the most recently consumed token
+    fn previous_span(&self) -> Span {
+        self.position.checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map(|token| token.span)
+            .unwrap_or_default()
+    }
+
     fn peek_next(&self) -> Option<&Token> {
         self.tokens.get(self.position + 1)
     }
@@ -441,76 +719,77 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn expect_identifier(&mut self) -> Result<String, String> {
-        if let Some(token) = self.peek() {
-            if let TokenType::Identifier(name) = &token.token_type {
-                let name_clone = name.clone();
-                self.advance();
-                return Ok(name_clone);
-            }
-            return Err(format!("Expected identifier, but found {:?}", token));
+    fn expect_identifier(&mut self) -> Result<String, Box<Diagnostic>> {
+        if let Some(token) = self.peek()
+            && let TokenType::Identifier(name) = &token.token_type
+        {
+            let name_clone = name.clone();
+            self.advance();
+            return Ok(name_clone);
         }
-        Err("Expected identifier, but found EOF".to_string())
+        Err(self.unexpected(vec!["identifier".to_string()]))
     }
 
-    fn expect_keyword(&mut self, expected: Keyword) -> Result<(), String> {
-        if let Some(token) = self.peek() {
-            if let TokenType::Keyword(k) = &token.token_type {
-                 if *k == expected {
-                    self.advance();
-                    return Ok(());
-                }
-            }
-            return Err(format!("Expected keyword {:?}, but found {:?}", expected, token));
+    fn expect_keyword(&mut self, expected: Keyword) -> Result<(), Box<Diagnostic>> {
+        if let Some(token) = self.peek()
+            && let TokenType::Keyword(k) = &token.token_type
+            && *k == expected
+        {
+            self.advance();
+            return Ok(());
         }
-        Err(format!("Expected keyword {:?}, but found EOF", expected))
+        Err(self.unexpected(vec![format!("`{}`", expected.as_str())]))
     }
 
-    fn expect_one_of_keywords(&mut self, expected: &[Keyword]) -> Result<Keyword, String> {
-        if let Some(token) = self.peek() {
-            if let TokenType::Keyword(k) = &token.token_type {
-                if expected.contains(k) {
-                    let keyword_clone = k.clone();
-                    self.advance();
-                    return Ok(keyword_clone);
-                }
-            }
-            return Err(format!("Expected one of {:?}, but found {:?}", expected, token));
+    fn expect_one_of_keywords(&mut self, expected: &[Keyword]) -> Result<Keyword, Box<Diagnostic>> {
+        if let Some(token) = self.peek()
+            && let TokenType::Keyword(k) = &token.token_type
+            && expected.contains(k)
+        {
+            let keyword_clone = k.clone();
+            self.advance();
+            return Ok(keyword_clone);
         }
-        Err(format!("Expected one of {:?}, but found EOF", expected))
+        Err(self.unexpected(expected.iter().map(|k| format!("`{}`", k.as_str())).collect()))
     }
 
     fn match_keyword(&mut self, expected: Keyword) -> bool {
-        if let Some(token) = self.peek() {
-             if let TokenType::Keyword(k) = &token.token_type {
-                if *k == expected {
-                    self.advance();
-                    return true;
-                }
-            }
+        if let Some(token) = self.peek()
+            && let TokenType::Keyword(k) = &token.token_type
+            && *k == expected
+        {
+            self.advance();
+            return true;
         }
         false
     }
 
     fn peek_keyword(&self, keywords: &[Keyword]) -> bool {
-        if let Some(token) = self.peek() {
-            if let TokenType::Keyword(k) = &token.token_type {
-                return keywords.contains(k);
-            }
+        if let Some(token) = self.peek()
+            && let TokenType::Keyword(k) = &token.token_type
+        {
+            return keywords.contains(k);
         }
         false
     }
 
-    fn expect_symbol(&mut self, expected: char) -> Result<(), String> {
+    fn expect_symbol(&mut self, expected: char) -> Result<(), Box<Diagnostic>> {
         if let Some(token) = self.peek() {
             if token.token_type == TokenType::Symbol(expected) {
                 self.advance();
                 Ok(())
             } else {
-                Err(format!("Expected symbol '{}', but found {:?}", expected, token))
+                let mut diagnostic = self.unexpected(vec![format!("`{}`", expected)]);
+                // A missing `;` is easier to spot right after the previous token than on the next line
+                let previous = self.previous_span();
+                if self.position > 0 && previous.end_line < token.span.line {
+                    diagnostic = Box::new(diagnostic.with_label(token.span, "unexpected token"));
+                    diagnostic.span = previous.after();
+                }
+                Err(diagnostic)
             }
         } else {
-            Err(format!("Expected symbol '{}', but found EOF", expected))
+            Err(self.unexpected(vec![format!("`{}`", expected)]))
         }
     }
 
@@ -528,30 +807,29 @@ impl<'a> Parser<'a> {
     }
 
     fn peek_symbol(&self, symbol: char) -> bool {
-        if let Some(token) = self.peek() {
-            if let TokenType::Symbol(s) = token.token_type {
-                return s == symbol;
-            }
+        if let Some(token) = self.peek()
+            && let TokenType::Symbol(s) = token.token_type
+        {
+            return s == symbol;
         }
         false
     }
 
     fn peek_next_symbol(&self, symbol: char) -> bool {
-        if let Some(token) = self.peek_next() {
-            if let TokenType::Symbol(s) = token.token_type {
-                return s == symbol;
-            }
+        if let Some(token) = self.peek_next()
+            && let TokenType::Symbol(s) = token.token_type
+        {
+            return s == symbol;
         }
         false
     }
 
     fn peek_op(&self) -> Option<char> {
-        if let Some(token) = self.peek() {
-            if let TokenType::Symbol(s) = token.token_type {
-                if "+-*/&|<>=".contains(s) {
-                    return Some(s);
-                }
-            }
+        if let Some(token) = self.peek()
+            && let TokenType::Symbol(s) = token.token_type
+            && "+-*/&|<>=".contains(s)
+        {
+            return Some(s);
         }
         None
     }
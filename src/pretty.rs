@@ -0,0 +1,205 @@
+use crate::parser::{
+    ClassNode, ClassVarKind, ExpressionNode, StatementNode, SubroutineCallNode, SubroutineDecNode, SubroutineKind,
+    TermKind, TermNode, Type,
+};
+use crate::span::Span;
+use crate::tokenizer::Comment;
+
+// Canonical Jack layout: four space indents, braces on the same line, one
+// statement per line and spaces around binary operators. Comments go on
+// their own line before the declaration or statement that follows them, or
+// at the end of the line they ended in the source.
+
+const INDENT: &str = "    ";
+
+/// Renders `class` as formatted Jack source, with the `comments` of its file.
+pub fn format_class(class: &ClassNode, comments: &[Comment]) -> String {
+    let mut printer = Printer { out: String::new(), depth: 0, comments };
+    printer.class(class);
+    printer.out
+}
+
+struct Printer<'a> {
+    out: String,
+    depth: usize,
+    /// Comments not printed yet, in source order
+    comments: &'a [Comment],
+}
+
+impl Printer<'_> {
+    fn class(&mut self, class: &ClassNode) {
+        self.comments_before(class.span.start);
+        self.line(&format!("class {} {{", class.name));
+        self.trailing_comments(class.span.line);
+        self.depth += 1;
+        for var_dec in &class.var_decs {
+            let kind = match var_dec.kind {
+                ClassVarKind::Static => "static",
+                ClassVarKind::Field => "field",
+            };
+            self.comments_before(var_dec.span.start);
+            self.line(&format!("{} {} {};", kind, var_dec.var_type, var_dec.names.join(", ")));
+            self.trailing_comments(var_dec.span.end_line);
+        }
+        for (i, subroutine) in class.subroutine_decs.iter().enumerate() {
+            if i > 0 || !class.var_decs.is_empty() {
+                self.blank_line();
+            }
+            self.subroutine(subroutine);
+        }
+        self.closing_brace(class.span);
+        self.comments_before(usize::MAX);
+    }
+
+    fn subroutine(&mut self, subroutine: &SubroutineDecNode) {
+        let kind = match subroutine.kind {
+            SubroutineKind::Constructor => "constructor",
+            SubroutineKind::Function => "function",
+            SubroutineKind::Method => "method",
+        };
+        let return_type = subroutine.return_type.as_ref().map_or("void".to_string(), Type::to_string);
+        let parameters: Vec<String> = subroutine.parameters.iter().map(|param| format!("{} {}", param.var_type, param.name)).collect();
+        self.comments_before(subroutine.span.start);
+        self.line(&format!("{} {} {}({}) {{", kind, return_type, subroutine.name, parameters.join(", ")));
+        self.trailing_comments(subroutine.span.line);
+        self.depth += 1;
+        for var_dec in &subroutine.body.var_decs {
+            self.comments_before(var_dec.span.start);
+            self.line(&format!("var {} {};", var_dec.var_type, var_dec.names.join(", ")));
+            self.trailing_comments(var_dec.span.end_line);
+        }
+        if !subroutine.body.var_decs.is_empty() && !subroutine.body.statements.is_empty() {
+            self.blank_line();
+        }
+        self.statements(&subroutine.body.statements);
+        self.closing_brace(subroutine.span);
+    }
+
+    fn statements(&mut self, statements: &[StatementNode]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &StatementNode) {
+        let span = statement.span();
+        self.comments_before(span.start);
+        match statement {
+            StatementNode::Let(let_stmt) => {
+                let target = match &let_stmt.index_expr {
+                    Some(index_expr) => format!("{}[{}]", let_stmt.var_name, expression(index_expr)),
+                    None => let_stmt.var_name.clone(),
+                };
+                self.line(&format!("let {} = {};", target, expression(&let_stmt.value_expr)));
+            }
+            StatementNode::If(if_stmt) => {
+                self.line(&format!("if ({}) {{", expression(&if_stmt.condition)));
+                self.trailing_comments(span.line);
+                self.depth += 1;
+                self.statements(&if_stmt.if_block);
+                match &if_stmt.else_block {
+                    Some(else_block) => {
+                        // Comments between the blocks stay in the first one
+                        let else_start = else_block.first().map_or(span.end, |statement| statement.span().start);
+                        self.comments_before(else_start);
+                        self.depth -= 1;
+                        self.line("} else {");
+                        self.depth += 1;
+                        self.statements(else_block);
+                        self.closing_brace(span);
+                    }
+                    None => self.closing_brace(span),
+                }
+                return;
+            }
+            StatementNode::While(while_stmt) => {
+                self.line(&format!("while ({}) {{", expression(&while_stmt.condition)));
+                self.trailing_comments(span.line);
+                self.depth += 1;
+                self.statements(&while_stmt.body);
+                self.closing_brace(span);
+                return;
+            }
+            StatementNode::Do(do_stmt) => self.line(&format!("do {};", subroutine_call(&do_stmt.call))),
+            StatementNode::Return(return_stmt) => match &return_stmt.value {
+                Some(value) => self.line(&format!("return {};", expression(value))),
+                None => self.line("return;"),
+            },
+        }
+        self.trailing_comments(span.end_line);
+    }
+
+    // Ends the block of the node at `span`, keeping the comments at the end of the block inside it
+    fn closing_brace(&mut self, span: Span) {
+        self.comments_before(span.end);
+        self.depth -= 1;
+        self.line("}");
+        self.trailing_comments(span.end_line);
+    }
+
+    // Prints the comments that start before `offset`, each on its own lines
+    fn comments_before(&mut self, offset: usize) {
+        while let Some((comment, rest)) = self.comments.split_first()
+            && comment.span.start < offset
+        {
+            self.comments = rest;
+            for (i, text) in comment.text.lines().enumerate() {
+                let text = text.trim();
+                // Continuation lines of a block comment line their `*` up with the opening one's
+                self.line(&if i > 0 && text.starts_with('*') { format!(" {}", text) } else { text.to_string() });
+            }
+        }
+    }
+
+    // Moves the comments that start on source line `line` to the end of the last printed line
+    fn trailing_comments(&mut self, line: usize) {
+        while let Some((comment, rest)) = self.comments.split_first()
+            && comment.span.line == line
+        {
+            self.comments = rest;
+            self.out.pop();
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn blank_line(&mut self) {
+        self.out.push('\n');
+    }
+}
+
+fn expression(expr: &ExpressionNode) -> String {
+    let mut text = term(&expr.initial_term);
+    for (op, operand) in &expr.operations {
+        text.push_str(&format!(" {} {}", op, term(operand)));
+    }
+    text
+}
+
+fn term(term_node: &TermNode) -> String {
+    match &term_node.kind {
+        TermKind::IntConst(n) => n.to_string(),
+        TermKind::StrConst(s) => format!("\"{}\"", s),
+        TermKind::KeywordConst(k) => k.as_str().to_string(),
+        TermKind::VarName(name) => name.clone(),
+        TermKind::ArrayAccess(name, index_expr) => format!("{}[{}]", name, expression(index_expr)),
+        TermKind::SubroutineCall(call) => subroutine_call(call),
+        TermKind::Parenthesized(expr) => format!("({})", expression(expr)),
+        TermKind::UnaryOp(op, inner) => format!("{}{}", op, term(inner)),
+    }
+}
+
+fn subroutine_call(call: &SubroutineCallNode) -> String {
+    let args: Vec<String> = call.args.iter().map(expression).collect();
+    match &call.receiver {
+        Some(receiver) => format!("{}.{}({})", receiver, call.name, args.join(", ")),
+        None => format!("{}({})", call.name, args.join(", ")),
+    }
+}
@@ -0,0 +1,165 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::span::Span;
+use std::path::Path;
+
+// Rustc-style rendering of diagnostics:
+//
+// error[E0100]: expected `;`, found `}`
+//  --> Main.jack:4:5
+//   |
+// 4 |     }
+//   |     ^ expected `;`
+//   |
+//   = note: ...
+//   = help: ...
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+const TAB_WIDTH: usize = 4;
+
+/// Renders `diagnostic` against the source it was reported for.
+pub fn render(diagnostic: &Diagnostic, path: &Path, source: &str, color: bool) -> String {
+    let style = Style { color };
+    let mut out = String::new();
+
+    let severity_color = match diagnostic.severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+    };
+    out.push_str(&format!(
+        "{}{}",
+        style.paint(severity_color, &format!("{}[{}]", diagnostic.severity, diagnostic.code)),
+        style.paint(BOLD, &format!(": {}", diagnostic.message)),
+    ));
+    out.push('\n');
+
+    let mut annotations = vec![Annotation {
+        span: diagnostic.span,
+        marker: '^',
+        message: primary_label(diagnostic),
+        color: severity_color,
+    }];
+    for label in &diagnostic.labels {
+        annotations.push(Annotation { span: label.span, marker: '-', message: label.message.clone(), color: BLUE });
+    }
+    annotations.sort_by_key(|a| (a.span.line, a.span.column));
+
+    let lines: Vec<&str> = source.lines().collect();
+    let gutter_width = annotations.iter().map(|a| a.span.line.to_string().len()).max().unwrap_or(1);
+    let gutter = " ".repeat(gutter_width);
+
+    // Diagnostics about a whole program, like those of an empty file, have no source to show
+    if source.is_empty() {
+        out.push_str(&format!("{}{} {}\n", gutter, style.paint(BLUE, "-->"), path.display()));
+    } else {
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            gutter,
+            style.paint(BLUE, "-->"),
+            path.display(),
+            diagnostic.span.line,
+            diagnostic.span.column,
+        ));
+        out.push_str(&format!("{} {}\n", gutter, style.paint(BLUE, "|")));
+
+        let mut previous_line = None;
+        for annotation in &annotations {
+            let line_number = annotation.span.line;
+            if previous_line != Some(line_number) {
+                if previous_line.is_some_and(|previous| line_number > previous + 1) {
+                    out.push_str(&format!("{}\n", style.paint(BLUE, "...")));
+                }
+                let text = lines.get(line_number.wrapping_sub(1)).copied().unwrap_or("");
+                out.push_str(&format!(
+                    "{} {}\n",
+                    style.paint(BLUE, &format!("{:>width$} |", line_number, width = gutter_width)),
+                    expand_tabs(text),
+                ));
+                previous_line = Some(line_number);
+            }
+
+            let text = lines.get(line_number.wrapping_sub(1)).copied().unwrap_or("");
+            let (offset, width) = underline_extent(text, annotation.span);
+            let mut underline = annotation.marker.to_string().repeat(width);
+            if !annotation.message.is_empty() {
+                underline.push(' ');
+                underline.push_str(&annotation.message);
+            }
+            out.push_str(&format!(
+                "{} {} {}{}\n",
+                gutter,
+                style.paint(BLUE, "|"),
+                " ".repeat(offset),
+                style.paint(annotation.color, &underline),
+            ));
+        }
+    }
+
+    if !diagnostic.notes.is_empty() || !diagnostic.helps.is_empty() {
+        out.push_str(&format!("{} {}\n", gutter, style.paint(BLUE, "|")));
+    }
+    for note in &diagnostic.notes {
+        out.push_str(&format!("{} {} {}\n", gutter, style.paint(BLUE, "="), style.paint(BOLD, &format!("note: {}", note))));
+    }
+    for help in &diagnostic.helps {
+        out.push_str(&format!("{} {} {}\n", gutter, style.paint(BLUE, "="), style.paint(BOLD, &format!("help: {}", help))));
+    }
+    out
+}
+
+struct Annotation {
+    span: Span,
+    marker: char,
+    message: String,
+    color: &'static str,
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// The text under the caret, e.g. "expected `;`" or "expected one of `static`, `field`"
+fn primary_label(diagnostic: &Diagnostic) -> String {
+    match diagnostic.expected.as_slice() {
+        [] => String::new(),
+        [single] => format!("expected {}", single),
+        many => format!("expected one of {}", many.join(", ")),
+    }
+}
+
+// Display column where the underline starts and how many markers to draw,
+// both measured after tab expansion. Spans running past the line are cut at its end.
+fn underline_extent(text: &str, span: Span) -> (usize, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let start = span.column.saturating_sub(1).min(chars.len());
+    let end = if span.end_line > span.line {
+        chars.len()
+    } else {
+        span.end_column.saturating_sub(1).clamp(start, chars.len())
+    };
+    let offset = display_width(&chars[..start]);
+    let width = display_width(&chars[start..end]).max(1);
+    (offset, width)
+}
+
+fn display_width(chars: &[char]) -> usize {
+    chars.iter().map(|&c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
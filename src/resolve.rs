@@ -0,0 +1,294 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::parser::{ClassNode, ExpressionNode, StatementNode, SubroutineCallNode, SubroutineKind, TermKind, TermNode, Type};
+use crate::signatures::{duplicate_subroutines, ProgramSignatures};
+use crate::span::Span;
+use crate::symbol_table::{Symbol, SymbolTable, VarKind};
+use crate::tokenizer::Keyword;
+
+/// Reports names in `class` that don't resolve: variables that aren't in
+/// scope, classes that are neither part of the program nor of the OS, and
+/// subroutines that their class doesn't declare. Calls that do resolve are
+/// checked for the number of arguments and for being the right kind of call,
+/// e.g. a method called through its class name.
+pub fn check_class(class: &ClassNode, signatures: &ProgramSignatures) -> Vec<Diagnostic> {
+    let (symbols, mut errors) = SymbolTable::for_class_partial(class);
+    errors.extend(duplicate_subroutines(class));
+    let mut resolver = Resolver {
+        signatures,
+        class_name: class.name.clone(),
+        symbols,
+        subroutine_kind: SubroutineKind::Function,
+        subroutine_name: String::new(),
+        errors,
+    };
+
+    for var_dec in &class.var_decs {
+        resolver.check_type(&var_dec.var_type, var_dec.span);
+    }
+    for subroutine in &class.subroutine_decs {
+        if let Some(return_type) = &subroutine.return_type {
+            resolver.check_type(return_type, subroutine.span);
+        }
+        for param in &subroutine.parameters {
+            resolver.check_type(&param.var_type, param.span);
+        }
+        for var_dec in &subroutine.body.var_decs {
+            resolver.check_type(&var_dec.var_type, var_dec.span);
+        }
+
+        if let Err(e) = resolver.symbols.enter_subroutine(subroutine) {
+            resolver.errors.push(*e);
+            continue;
+        }
+        resolver.subroutine_kind = subroutine.kind.clone();
+        resolver.subroutine_name = subroutine.name.clone();
+        resolver.check_statements(&subroutine.body.statements);
+    }
+    resolver.errors
+}
+
+struct Resolver<'a> {
+    signatures: &'a ProgramSignatures,
+    class_name: String,
+    symbols: SymbolTable,
+    subroutine_kind: SubroutineKind,
+    subroutine_name: String,
+    errors: Vec<Diagnostic>,
+}
+
+// How a call names its target
+enum CallForm {
+    /// `foo()`
+    Unqualified,
+    /// `var.foo()`
+    Object,
+    /// `Class.foo()`
+    Class,
+}
+
+impl Resolver<'_> {
+    fn check_type(&mut self, var_type: &Type, span: Span) {
+        if let Type::ClassName(class_name) = var_type
+            && !self.signatures.has_class(class_name)
+        {
+            let mut error = Diagnostic::error(codes::UNDEFINED_CLASS, format!("cannot find class `{}`", class_name), span);
+            if let Some(similar) = similar_name(class_name, self.signatures.class_names()) {
+                error = error.with_help(format!("a class with a similar name exists: `{}`", similar));
+            }
+            self.errors.push(error);
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[StatementNode]) {
+        for statement in statements {
+            match statement {
+                StatementNode::Let(let_stmt) => {
+                    self.check_variable(&let_stmt.var_name, let_stmt.name_span);
+                    if let Some(index_expr) = &let_stmt.index_expr {
+                        self.check_expression(index_expr);
+                    }
+                    self.check_expression(&let_stmt.value_expr);
+                }
+                StatementNode::If(if_stmt) => {
+                    self.check_expression(&if_stmt.condition);
+                    self.check_statements(&if_stmt.if_block);
+                    if let Some(else_block) = &if_stmt.else_block {
+                        self.check_statements(else_block);
+                    }
+                }
+                StatementNode::While(while_stmt) => {
+                    self.check_expression(&while_stmt.condition);
+                    self.check_statements(&while_stmt.body);
+                }
+                StatementNode::Do(do_stmt) => self.check_call(&do_stmt.call),
+                StatementNode::Return(return_stmt) => {
+                    if let Some(value) = &return_stmt.value {
+                        self.check_expression(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expr: &ExpressionNode) {
+        self.check_term(&expr.initial_term);
+        for (_, term) in &expr.operations {
+            self.check_term(term);
+        }
+    }
+
+    fn check_term(&mut self, term: &TermNode) {
+        match &term.kind {
+            TermKind::KeywordConst(Keyword::This) if self.subroutine_kind == SubroutineKind::Function => {
+                self.errors.push(
+                    Diagnostic::error(
+                        codes::THIS_OUTSIDE_METHOD,
+                        format!("`this` cannot be used in function `{}`", self.subroutine_name),
+                        term.span,
+                    )
+                    .with_note("functions are not called on an object, `this` is only available in methods and constructors"),
+                );
+            }
+            TermKind::IntConst(_) | TermKind::StrConst(_) | TermKind::KeywordConst(_) => {}
+            TermKind::VarName(name) => self.check_variable(name, term.span),
+            TermKind::ArrayAccess(name, index_expr) => {
+                self.check_variable(name, term.span);
+                self.check_expression(index_expr);
+            }
+            TermKind::SubroutineCall(call) => self.check_call(call),
+            TermKind::Parenthesized(expr) => self.check_expression(expr),
+            TermKind::UnaryOp(_, inner) => self.check_term(inner),
+        }
+    }
+
+    fn check_variable(&mut self, name: &str, span: Span) {
+        if let Some(symbol) = self.symbols.lookup(name) {
+            let symbol = symbol.clone();
+            self.check_field_access(name, &symbol, span);
+            return;
+        }
+        let mut error = Diagnostic::error(
+            codes::UNDEFINED_VARIABLE,
+            format!("cannot find variable `{}` in class `{}`", name, self.class_name),
+            span,
+        );
+        if let Some(similar) = similar_name(name, self.symbols.names()) {
+            error = error.with_help(format!("a variable with a similar name exists: `{}`", similar));
+        }
+        self.errors.push(error);
+    }
+
+    // A field is read through `this`, which functions don't have
+    fn check_field_access(&mut self, name: &str, symbol: &Symbol, span: Span) {
+        if symbol.kind == VarKind::Field && self.subroutine_kind == SubroutineKind::Function {
+            self.errors.push(
+                Diagnostic::error(
+                    codes::THIS_OUTSIDE_METHOD,
+                    format!("field `{}` cannot be used in function `{}`", name, self.subroutine_name),
+                    span,
+                )
+                .with_label(symbol.span, format!("`{}` is declared here", name))
+                .with_note("fields belong to an object, they are only available in methods and constructors"),
+            );
+        }
+    }
+
+    fn check_call(&mut self, call: &SubroutineCallNode) {
+        for arg in &call.args {
+            self.check_expression(arg);
+        }
+
+        let (class_name, form) = match (&call.receiver, call.receiver_span) {
+            (Some(receiver), Some(receiver_span)) => match self.symbols.lookup(receiver).cloned() {
+                Some(symbol) => {
+                    self.check_field_access(receiver, &symbol, receiver_span);
+                    // Calls on primitives are the type checker's to report, unknown classes were reported at the declaration
+                    match &symbol.var_type {
+                        Type::ClassName(class_name) if self.signatures.has_class(class_name) => (class_name.clone(), CallForm::Object),
+                        _ => return,
+                    }
+                }
+                None if self.signatures.has_class(receiver) => (receiver.clone(), CallForm::Class),
+                None => {
+                    let mut error = Diagnostic::error(
+                        codes::UNDEFINED_CLASS,
+                        format!("cannot find variable or class `{}`", receiver),
+                        receiver_span,
+                    );
+                    let candidates = self.symbols.names().chain(self.signatures.class_names());
+                    if let Some(similar) = similar_name(receiver, candidates) {
+                        error = error.with_help(format!("a variable or class with a similar name exists: `{}`", similar));
+                    }
+                    self.errors.push(error);
+                    return;
+                }
+            },
+            _ => (self.class_name.clone(), CallForm::Unqualified),
+        };
+
+        let Some(signature) = self.signatures.subroutine(&class_name, &call.name) else {
+            let mut error = Diagnostic::error(
+                codes::UNDEFINED_SUBROUTINE,
+                format!("cannot find subroutine `{}` in class `{}`", call.name, class_name),
+                call.name_span,
+            );
+            if let Some(similar) = similar_name(&call.name, self.signatures.subroutine_names(&class_name)) {
+                error = error.with_help(format!("a subroutine with a similar name exists: `{}`", similar));
+            }
+            self.errors.push(error);
+            return;
+        };
+
+        let target = format!("{}.{}", class_name, call.name);
+        let is_method = signature.kind == SubroutineKind::Method;
+        let kind_error = match form {
+            CallForm::Class if is_method => Some(
+                Diagnostic::error(codes::INVALID_CALL_KIND, format!("method `{}` cannot be called through its class", target), call.span)
+                    .with_help(format!("call it on an object of class `{}`", class_name)),
+            ),
+            CallForm::Object if !is_method => Some(
+                Diagnostic::error(codes::INVALID_CALL_KIND, format!("`{}` is not a method and cannot be called on an object", target), call.span)
+                    .with_help(format!("call it through its class: `{}(...)`", target)),
+            ),
+            CallForm::Unqualified if !is_method => Some(
+                Diagnostic::error(codes::INVALID_CALL_KIND, format!("`{}` is not a method and must be called through its class", target), call.span)
+                    .with_note("an unqualified call is a method call on `this`")
+                    .with_help(format!("write `{}(...)`", target)),
+            ),
+            CallForm::Unqualified if self.subroutine_kind == SubroutineKind::Function => Some(
+                Diagnostic::error(
+                    codes::INVALID_CALL_KIND,
+                    format!("method `{}` cannot be called from function `{}`", call.name, self.subroutine_name),
+                    call.span,
+                )
+                .with_note("an unqualified call is a method call on `this`, which functions don't have"),
+            ),
+            _ => None,
+        };
+        if let Some(error) = kind_error {
+            self.errors.push(error);
+        }
+
+        if call.args.len() != signature.params.len() {
+            self.errors.push(Diagnostic::error(
+                codes::ARGUMENT_COUNT_MISMATCH,
+                format!(
+                    "`{}` takes {} argument{} but {} {} supplied",
+                    target,
+                    signature.params.len(),
+                    if signature.params.len() == 1 { "" } else { "s" },
+                    call.args.len(),
+                    if call.args.len() == 1 { "was" } else { "were" },
+                ),
+                call.span,
+            ));
+        }
+    }
+}
+
+// The candidate closest to `name`, if it is close enough to plausibly be a typo.
+// Ties go to the alphabetically first candidate so that the suggestion is stable.
+fn similar_name<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(&name.to_lowercase(), &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance, compared case-insensitively by the caller
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,141 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::json::Json;
+use crate::lint::{Level, LintConfig, LINTS};
+use crate::span::Span;
+use std::path::Path;
+
+// A SARIF 2.1.0 log of the diagnostics of `check` and `compile`, for code
+// review tools that annotate sources with static analysis results. Error
+// codes and lints both become rules, listed in full so that a tool can show
+// what each one means even when a run found nothing.
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The log for one run over `files`, each with the diagnostics reported in
+/// it. Lint rules carry the level they were configured with.
+pub fn log(files: &[(&Path, &[Diagnostic])], lints: &LintConfig) -> Json {
+    let mut rules: Vec<(&str, &str, &str)> = codes::DESCRIPTIONS.iter().map(|&(code, description)| (code, description, "error")).collect();
+    for lint in LINTS {
+        let level = match lints.level(lint) {
+            Level::Allow => "none",
+            Level::Warn => "warning",
+            Level::Deny => "error",
+        };
+        rules.push((lint.name, lint.description, level));
+    }
+
+    let mut results = Vec::new();
+    for (path, diagnostics) in files {
+        for diagnostic in diagnostics.iter() {
+            let rule_index = rules.iter().position(|(id, _, _)| *id == diagnostic.code);
+            results.push(result(diagnostic, path, rule_index));
+        }
+    }
+
+    let rules = rules
+        .iter()
+        .map(|&(id, description, level)| {
+            Json::Object(vec![
+                ("id", Json::string(id)),
+                ("shortDescription", text(description)),
+                ("defaultConfiguration", Json::Object(vec![("level", Json::string(level))])),
+            ])
+        })
+        .collect();
+    let driver = Json::Object(vec![
+        ("name", Json::string(env!("CARGO_PKG_NAME"))),
+        ("version", Json::string(env!("CARGO_PKG_VERSION"))),
+        ("rules", Json::Array(rules)),
+    ]);
+    let run = Json::Object(vec![
+        ("tool", Json::Object(vec![("driver", driver)])),
+        // Span columns count characters, not UTF-16 code units
+        ("columnKind", Json::string("unicodeCodePoints")),
+        ("results", Json::Array(results)),
+    ]);
+    Json::Object(vec![("$schema", Json::string(SCHEMA)), ("version", Json::string("2.1.0")), ("runs", Json::Array(vec![run]))])
+}
+
+// Notes and helps have no place of their own in a result, so they are added
+// to the message the way they are printed under a rendered diagnostic
+fn result(diagnostic: &Diagnostic, path: &Path, rule_index: Option<usize>) -> Json {
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    for help in &diagnostic.helps {
+        message.push_str(&format!("\nhelp: {}", help));
+    }
+
+    let related = diagnostic
+        .labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            Json::Object(vec![
+                ("id", Json::Number(i)),
+                ("physicalLocation", physical_location(path, label.span)),
+                ("message", text(&label.message)),
+            ])
+        })
+        .collect();
+
+    let mut fields = vec![("ruleId", Json::string(diagnostic.code))];
+    if let Some(index) = rule_index {
+        fields.push(("ruleIndex", Json::Number(index)));
+    }
+    fields.extend([
+        ("level", Json::string(diagnostic.severity.to_string())),
+        ("message", text(&message)),
+        ("locations", Json::Array(vec![Json::Object(vec![("physicalLocation", physical_location(path, diagnostic.span))])])),
+        ("relatedLocations", Json::Array(related)),
+    ]);
+    Json::Object(fields)
+}
+
+fn physical_location(path: &Path, span: Span) -> Json {
+    Json::Object(vec![
+        ("artifactLocation", artifact_location(path)),
+        (
+            "region",
+            Json::Object(vec![
+                ("startLine", Json::Number(span.line)),
+                ("startColumn", Json::Number(span.column)),
+                ("endLine", Json::Number(span.end_line)),
+                ("endColumn", Json::Number(span.end_column)),
+                ("byteOffset", Json::Number(span.start)),
+                ("byteLength", Json::Number(span.end - span.start)),
+            ]),
+        ),
+    ])
+}
+
+// Relative paths are resolved against the checkout by the consuming tool
+fn artifact_location(path: &Path) -> Json {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    let uri = percent_encode(&path.to_string_lossy().replace('\\', "/"));
+    if path.is_absolute() {
+        // Windows paths start with the drive rather than a slash
+        let slash = if uri.starts_with('/') { "" } else { "/" };
+        Json::Object(vec![("uri", Json::string(format!("file://{}{}", slash, uri)))])
+    } else {
+        Json::Object(vec![("uri", Json::string(uri)), ("uriBaseId", Json::string("%SRCROOT%"))])
+    }
+}
+
+// Every byte of the path but letters, digits, `-._~` and the `/` between
+// segments is escaped, so spaces, `#` and `%` don't change what the URI means
+fn percent_encode(path: &str) -> String {
+    let mut out = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => out.push(char::from(byte)),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn text(text: &str) -> Json {
+    Json::Object(vec![("text", Json::string(text))])
+}
@@ -0,0 +1,155 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::parser::{ClassNode, SubroutineKind, Type};
+use crate::span::Span;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubroutineSignature {
+    pub kind: SubroutineKind,
+    pub return_type: Option<Type>, // None for void
+    pub params: Vec<Type>,
+}
+
+/// The subroutines of every class in the program, so that checks on one class
+/// can look at calls into the others.
+#[derive(Debug, Default)]
+pub struct ProgramSignatures {
+    classes: HashMap<String, HashMap<String, SubroutineSignature>>,
+}
+
+// The Jack OS API as (class, kind, return type, subroutine, parameter types)
+const OS_SUBROUTINES: &[(&str, SubroutineKind, &str, &str, &[&str])] = &[
+    ("Math", SubroutineKind::Function, "void", "init", &[]),
+    ("Math", SubroutineKind::Function, "int", "abs", &["int"]),
+    ("Math", SubroutineKind::Function, "int", "multiply", &["int", "int"]),
+    ("Math", SubroutineKind::Function, "int", "divide", &["int", "int"]),
+    ("Math", SubroutineKind::Function, "int", "min", &["int", "int"]),
+    ("Math", SubroutineKind::Function, "int", "max", &["int", "int"]),
+    ("Math", SubroutineKind::Function, "int", "sqrt", &["int"]),
+    ("String", SubroutineKind::Constructor, "String", "new", &["int"]),
+    ("String", SubroutineKind::Method, "void", "dispose", &[]),
+    ("String", SubroutineKind::Method, "int", "length", &[]),
+    ("String", SubroutineKind::Method, "char", "charAt", &["int"]),
+    ("String", SubroutineKind::Method, "void", "setCharAt", &["int", "char"]),
+    ("String", SubroutineKind::Method, "String", "appendChar", &["char"]),
+    ("String", SubroutineKind::Method, "void", "eraseLastChar", &[]),
+    ("String", SubroutineKind::Method, "int", "intValue", &[]),
+    ("String", SubroutineKind::Method, "void", "setInt", &["int"]),
+    ("String", SubroutineKind::Function, "char", "backSpace", &[]),
+    ("String", SubroutineKind::Function, "char", "doubleQuote", &[]),
+    ("String", SubroutineKind::Function, "char", "newLine", &[]),
+    ("Array", SubroutineKind::Function, "Array", "new", &["int"]),
+    ("Array", SubroutineKind::Method, "void", "dispose", &[]),
+    ("Output", SubroutineKind::Function, "void", "init", &[]),
+    ("Output", SubroutineKind::Function, "void", "moveCursor", &["int", "int"]),
+    ("Output", SubroutineKind::Function, "void", "printChar", &["char"]),
+    ("Output", SubroutineKind::Function, "void", "printString", &["String"]),
+    ("Output", SubroutineKind::Function, "void", "printInt", &["int"]),
+    ("Output", SubroutineKind::Function, "void", "println", &[]),
+    ("Output", SubroutineKind::Function, "void", "backSpace", &[]),
+    ("Screen", SubroutineKind::Function, "void", "init", &[]),
+    ("Screen", SubroutineKind::Function, "void", "clearScreen", &[]),
+    ("Screen", SubroutineKind::Function, "void", "setColor", &["boolean"]),
+    ("Screen", SubroutineKind::Function, "void", "drawPixel", &["int", "int"]),
+    ("Screen", SubroutineKind::Function, "void", "drawLine", &["int", "int", "int", "int"]),
+    ("Screen", SubroutineKind::Function, "void", "drawRectangle", &["int", "int", "int", "int"]),
+    ("Screen", SubroutineKind::Function, "void", "drawCircle", &["int", "int", "int"]),
+    ("Keyboard", SubroutineKind::Function, "void", "init", &[]),
+    ("Keyboard", SubroutineKind::Function, "char", "keyPressed", &[]),
+    ("Keyboard", SubroutineKind::Function, "char", "readChar", &[]),
+    ("Keyboard", SubroutineKind::Function, "String", "readLine", &["String"]),
+    ("Keyboard", SubroutineKind::Function, "int", "readInt", &["String"]),
+    ("Memory", SubroutineKind::Function, "void", "init", &[]),
+    ("Memory", SubroutineKind::Function, "int", "peek", &["int"]),
+    ("Memory", SubroutineKind::Function, "void", "poke", &["int", "int"]),
+    ("Memory", SubroutineKind::Function, "Array", "alloc", &["int"]),
+    ("Memory", SubroutineKind::Function, "void", "deAlloc", &["Array"]),
+    ("Sys", SubroutineKind::Function, "void", "init", &[]),
+    ("Sys", SubroutineKind::Function, "void", "halt", &[]),
+    ("Sys", SubroutineKind::Function, "void", "error", &["int"]),
+    ("Sys", SubroutineKind::Function, "void", "wait", &["int"]),
+];
+
+impl ProgramSignatures {
+    /// Collects the signatures of `classes` plus those of the OS classes.
+    /// A class of the program replaces the OS class of the same name.
+    pub fn from_classes<'a>(classes: impl IntoIterator<Item = &'a ClassNode>) -> Self {
+        let mut signatures = ProgramSignatures::default();
+        for class in classes {
+            let subroutines = signatures.classes.entry(class.name.clone()).or_default();
+            for subroutine in &class.subroutine_decs {
+                let signature = SubroutineSignature {
+                    kind: subroutine.kind.clone(),
+                    return_type: subroutine.return_type.clone(),
+                    params: subroutine.parameters.iter().map(|param| param.var_type.clone()).collect(),
+                };
+                // Calls are checked against the first one, see `duplicate_subroutines`
+                subroutines.entry(subroutine.name.clone()).or_insert(signature);
+            }
+        }
+
+        let mut os_classes: HashMap<String, HashMap<String, SubroutineSignature>> = HashMap::new();
+        for (class_name, kind, return_type, name, params) in OS_SUBROUTINES {
+            let signature = SubroutineSignature {
+                kind: kind.clone(),
+                return_type: os_type(return_type),
+                params: params.iter().filter_map(|param| os_type(param)).collect(),
+            };
+            os_classes.entry(class_name.to_string()).or_default().insert(name.to_string(), signature);
+        }
+        for (class_name, subroutines) in os_classes {
+            signatures.classes.entry(class_name).or_insert(subroutines);
+        }
+        signatures
+    }
+
+    pub fn subroutine(&self, class_name: &str, name: &str) -> Option<&SubroutineSignature> {
+        self.classes.get(class_name)?.get(name)
+    }
+
+    pub fn has_class(&self, class_name: &str) -> bool {
+        self.classes.contains_key(class_name)
+    }
+
+    pub fn class_names(&self) -> impl Iterator<Item = &str> {
+        self.classes.keys().map(String::as_str)
+    }
+
+    /// The subroutine names of `class_name`, empty for unknown classes.
+    pub fn subroutine_names(&self, class_name: &str) -> impl Iterator<Item = &str> {
+        self.classes.get(class_name).into_iter().flat_map(|subroutines| subroutines.keys().map(String::as_str))
+    }
+}
+
+/// An error for each subroutine of `class` declared again after its first
+/// declaration, the one `ProgramSignatures` keeps.
+pub fn duplicate_subroutines(class: &ClassNode) -> Vec<Diagnostic> {
+    let mut declared: HashMap<&str, Span> = HashMap::new();
+    let mut errors = Vec::new();
+    for subroutine in &class.subroutine_decs {
+        match declared.get(subroutine.name.as_str()) {
+            Some(previous) => errors.push(
+                Diagnostic::error(
+                    codes::DUPLICATE_SUBROUTINE,
+                    format!("subroutine `{}` is already declared in class `{}`", subroutine.name, class.name),
+                    subroutine.span,
+                )
+                .with_label(*previous, "previous declaration here"),
+            ),
+            None => {
+                declared.insert(&subroutine.name, subroutine.span);
+            }
+        }
+    }
+    errors
+}
+
+fn os_type(name: &str) -> Option<Type> {
+    match name {
+        "void" => None,
+        "int" => Some(Type::Int),
+        "char" => Some(Type::Char),
+        "boolean" => Some(Type::Boolean),
+        class_name => Some(Type::ClassName(class_name.to_string())),
+    }
+}
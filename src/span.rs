@@ -0,0 +1,75 @@
+/// A region of source text.
+///
+/// `start`/`end` are byte offsets into the file (end exclusive). Lines and
+/// columns start at 1 and count characters; `end_column` is the column just
+/// past the last character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+// The start of the file, for diagnostics with no token to point at, like those for an empty file
+impl Default for Span {
+    fn default() -> Self {
+        Span { start: 0, end: 0, line: 1, column: 1, end_line: 1, end_column: 1 }
+    }
+}
+
+impl Span {
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+            column: self.column,
+            end_line: other.end_line,
+            end_column: other.end_column,
+        }
+    }
+
+    /// An empty span just past the end of `self`, used for things like a missing `;`.
+    pub fn after(self) -> Span {
+        Span {
+            start: self.end,
+            end: self.end,
+            line: self.end_line,
+            column: self.end_column,
+            end_line: self.end_line,
+            end_column: self.end_column,
+        }
+    }
+}
+
+/// The non-blank lines of a line-based language like VM code or Hack
+/// assembly, with `//` comments and surrounding whitespace removed, each with
+/// the span of what is left.
+pub fn code_lines(source: &str) -> Vec<(&str, Span)> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    for (i, line) in source.split('\n').enumerate() {
+        let code = line.split("//").next().unwrap_or_default().trim_end();
+        let indent = code.len() - code.trim_start().len();
+        let code = code.trim_start();
+        if !code.is_empty() {
+            let start = line_start + indent;
+            let column = line[..indent].chars().count() + 1;
+            let span = Span {
+                start,
+                end: start + code.len(),
+                line: i + 1,
+                column,
+                end_line: i + 1,
+                end_column: column + code.chars().count(),
+            };
+            lines.push((code, span));
+        }
+        line_start += line.len() + 1;
+    }
+    lines
+}
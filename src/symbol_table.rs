@@ -0,0 +1,120 @@
+use crate::parser::{ClassNode, ClassVarKind, SubroutineDecNode, SubroutineKind, Type};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::Span;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VarKind {
+    Static,
+    Field,
+    Argument,
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub var_type: Type,
+    pub kind: VarKind,
+    pub index: usize,
+    /// Where the variable was declared
+    pub span: Span,
+}
+
+/// Resolves variable names to their kind and running index.
+///
+/// The class scope holds statics and fields and lives as long as the table.
+/// The subroutine scope holds arguments and locals and is reset for every
+/// subroutine; names in it shadow class-level names.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    class_name: String,
+    class_scope: HashMap<String, Symbol>,
+    subroutine_scope: HashMap<String, Symbol>,
+    counts: HashMap<VarKind, usize>,
+}
+
+impl SymbolTable {
+    /// Builds a table with the class scope filled from the class's `static` and `field` declarations.
+    pub fn for_class(class: &ClassNode) -> Result<Self, Box<Diagnostic>> {
+        let (table, mut errors) = Self::for_class_partial(class);
+        if errors.is_empty() {
+            Ok(table)
+        } else {
+            Err(Box::new(errors.remove(0)))
+        }
+    }
+
+    /// Like `for_class`, but leaves duplicate declarations out of the table
+    /// and returns an error for each, so that checks can go on without them.
+    pub fn for_class_partial(class: &ClassNode) -> (Self, Vec<Diagnostic>) {
+        let mut table = SymbolTable { class_name: class.name.clone(), ..Default::default() };
+        let mut errors = Vec::new();
+        for var_dec in &class.var_decs {
+            let kind = match var_dec.kind {
+                ClassVarKind::Static => VarKind::Static,
+                ClassVarKind::Field => VarKind::Field,
+            };
+            for name in &var_dec.names {
+                if let Err(e) = table.define(name, var_dec.var_type.clone(), kind, var_dec.span) {
+                    errors.push(*e);
+                }
+            }
+        }
+        (table, errors)
+    }
+
+    /// Resets the subroutine scope and fills it with the subroutine's parameters and locals.
+    pub fn enter_subroutine(&mut self, subroutine: &SubroutineDecNode) -> Result<(), Box<Diagnostic>> {
+        self.start_subroutine(&subroutine.kind, subroutine.span);
+        for param in &subroutine.parameters {
+            self.define(&param.name, param.var_type.clone(), VarKind::Argument, param.span)?;
+        }
+        for var_dec in &subroutine.body.var_decs {
+            for name in &var_dec.names {
+                self.define(name, var_dec.var_type.clone(), VarKind::Local, var_dec.span)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Clears the subroutine scope. Methods get the implicit `this` as argument 0.
+    pub fn start_subroutine(&mut self, kind: &SubroutineKind, span: Span) {
+        self.subroutine_scope.clear();
+        self.counts.remove(&VarKind::Argument);
+        self.counts.remove(&VarKind::Local);
+        if *kind == SubroutineKind::Method {
+            let this_type = Type::ClassName(self.class_name.clone());
+            self.subroutine_scope.insert("this".to_string(), Symbol { var_type: this_type, kind: VarKind::Argument, index: 0, span });
+            self.counts.insert(VarKind::Argument, 1);
+        }
+    }
+
+    pub fn define(&mut self, name: &str, var_type: Type, kind: VarKind, span: Span) -> Result<(), Box<Diagnostic>> {
+        let scope = match kind {
+            VarKind::Static | VarKind::Field => &mut self.class_scope,
+            VarKind::Argument | VarKind::Local => &mut self.subroutine_scope,
+        };
+        if let Some(previous) = scope.get(name) {
+            return Err(Diagnostic::error(codes::DUPLICATE_VARIABLE, format!("variable `{}` is already declared in this scope", name), span)
+                .with_label(previous.span, "previous declaration here")
+                .into());
+        }
+        let count = self.counts.entry(kind).or_insert(0);
+        scope.insert(name.to_string(), Symbol { var_type, kind, index: *count, span });
+        *count += 1;
+        Ok(())
+    }
+
+    pub fn var_count(&self, kind: VarKind) -> usize {
+        self.counts.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.subroutine_scope.get(name).or_else(|| self.class_scope.get(name))
+    }
+
+    /// The names of all variables in scope, without the implicit `this`.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.subroutine_scope.keys().chain(self.class_scope.keys()).map(String::as_str).filter(|name| *name != "this")
+    }
+}
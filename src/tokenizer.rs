@@ -1,9 +1,16 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::Span;
+
+/// Largest integer constant allowed by the Jack spec. The tokenizer accepts
+/// one more than this so that the parser can support `-32768`.
+pub const MAX_INT_CONST: u16 = 32767;
+
 // Keep your existing Token, TokenType, and Keyword structs. They are perfect.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub value: String,
-    pub line_number: usize,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -13,6 +20,8 @@ pub enum TokenType {
     IntConst(u16),
     StrConst(String),
     Identifier(String),
+    /// Malformed input that has already been reported; `value` holds the offending text
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,59 +31,146 @@ pub enum Keyword {
     This, Let, Do, If, Else, While, Return
 }
 
+impl Token {
+    /// Short human readable description used in error messages, e.g. "keyword `let`".
+    pub fn describe(&self) -> String {
+        match &self.token_type {
+            TokenType::Keyword(k) => format!("keyword `{}`", k.as_str()),
+            TokenType::Symbol(c) => format!("`{}`", c),
+            TokenType::IntConst(n) => format!("integer constant `{}`", n),
+            TokenType::StrConst(s) => format!("string constant \"{}\"", s),
+            TokenType::Identifier(name) => format!("identifier `{}`", name),
+            TokenType::Error => format!("invalid token `{}`", self.value),
+        }
+    }
+}
+
+impl Keyword {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Class       => "class",
+            Keyword::Constructor => "constructor",
+            Keyword::Function    => "function",
+            Keyword::Method      => "method",
+            Keyword::Field       => "field",
+            Keyword::Static      => "static",
+            Keyword::Var         => "var",
+            Keyword::Int         => "int",
+            Keyword::Char        => "char",
+            Keyword::Boolean     => "boolean",
+            Keyword::Void        => "void",
+            Keyword::True        => "true",
+            Keyword::False       => "false",
+            Keyword::Null        => "null",
+            Keyword::This        => "this",
+            Keyword::Let         => "let",
+            Keyword::Do          => "do",
+            Keyword::If          => "if",
+            Keyword::Else        => "else",
+            Keyword::While       => "while",
+            Keyword::Return      => "return",
+        }
+    }
+}
+
+
+/// A `//` or `/* */` comment, delimiters included. The parser never sees
+/// comments, the formatter puts them back between the nodes it prints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
+
+/// Splits `content` into tokens. Lexical errors don't stop the scan: each one is
+/// reported and its text becomes a `TokenType::Error` token, so the returned
+/// stream covers the whole input. An unclosed `/*` comment is reported and
+/// dropped like a closed one.
+pub fn tokenizer(content: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+    let (tokens, _, errors) = scan(content);
+    (tokens, errors)
+}
 
-pub fn tokenizer(content: &str) -> Result<Vec<Token>, String> {
+/// The comments in `content`, in source order.
+pub fn comments(content: &str) -> Vec<Comment> {
+    scan(content).1
+}
+
+fn scan(content: &str) -> (Vec<Token>, Vec<Comment>, Vec<Diagnostic>) {
     let mut tokens = Vec::new();
+    let mut comments = Vec::new();
+    let mut errors = Vec::new();
     let mut line_number = 1;
     let chars = content.chars().collect::<Vec<char>>();
+    // Byte offset of every char, plus one past the end
+    let offsets = content.char_indices().map(|(offset, _)| offset)
+        .chain(std::iter::once(content.len()))
+        .collect::<Vec<usize>>();
+    let mut line_start = 0; // Index of the first char on the current line
     let mut i = 0;
 
     while i < chars.len() {
         let c = chars[i];
+        let start = i;
 
         // 1. Handle Whitespace
         if c.is_whitespace() {
             if c == '\n' {
                 line_number += 1;
+                line_start = i + 1;
             }
             i += 1;
             continue;
         }
 
         // 2. Handle Comments
-        if c == '/' {
-            if i + 1 < chars.len() {
+        if c == '/'
+            && i + 1 < chars.len() {
                 let next_char = chars[i + 1];
                 if next_char == '/' { // Single-line comment
                     i += 2;
                     while i < chars.len() && chars[i] != '\n' {
                         i += 1;
                     }
+                    let text = chars[start..i].iter().collect::<String>().trim_end().to_string();
+                    comments.push(Comment { text, span: make_span(&offsets, start, i, line_number, line_start) });
                     continue; // Let the main loop handle the newline
                 } else if next_char == '*' { // Multi-line comment
-                    let start_line = line_number;
+                    let opening = make_span(&offsets, start, start + 2, line_number, line_start);
+                    let (opening_line, opening_column) = (line_number, start - line_start + 1);
                     i += 2;
                     while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
                         if chars[i] == '\n' {
                             line_number += 1;
+                            line_start = i + 1;
                         }
                         i += 1;
                     }
                     if i + 1 >= chars.len() {
-                        return Err(format!("Unterminated multi-line comment starting on line {}", start_line));
+                        errors.push(Diagnostic::error(codes::UNTERMINATED_COMMENT, "unterminated block comment", opening)
+                            .with_note("block comments must be closed with `*/`"));
+                        break;
                     }
                     i += 2; // Consume "*/"
+                    let span = Span {
+                        start: offsets[start],
+                        end: offsets[i],
+                        line: opening_line,
+                        column: opening_column,
+                        end_line: line_number,
+                        end_column: i - line_start + 1,
+                    };
+                    comments.push(Comment { text: chars[start..i].iter().collect(), span });
                     continue;
                 }
             }
-        }
 
         // 3. Handle Symbols
         if "{}()[].,;+-*/&|<>=~".contains(c) {
             tokens.push(Token {
                 token_type: TokenType::Symbol(c),
                 value: c.to_string(),
-                line_number,
+                span: make_span(&offsets, start, i + 1, line_number, line_start),
             });
             i += 1;
             continue;
@@ -84,21 +180,31 @@ pub fn tokenizer(content: &str) -> Result<Vec<Token>, String> {
         if c == '"' {
             i += 1; // Consume opening quote
             let mut s = String::new();
-            while i < chars.len() && chars[i] != '"' {
-                if chars[i] == '\n' {
-                     return Err(format!("Unterminated string on line {}", line_number));
-                }
+            while i < chars.len() && chars[i] != '"' && chars[i] != '\n' {
                 s.push(chars[i]);
                 i += 1;
             }
-            if i >= chars.len() {
-                return Err(format!("Unterminated string on line {}", line_number));
+            if i >= chars.len() || chars[i] == '\n' {
+                // Leave the newline to the main loop
+                let span = make_span(&offsets, start, i, line_number, line_start);
+                errors.push(Diagnostic::error(codes::UNTERMINATED_STRING, "unterminated string constant", span)
+                    .with_note("string constants cannot contain newlines"));
+                tokens.push(Token { token_type: TokenType::Error, value: format!("\"{}", s), span });
+                continue;
+            }
+            // Characters are pushed as integer constants
+            for (j, ch) in chars.iter().enumerate().take(i).skip(start + 1) {
+                if *ch as u32 > u32::from(MAX_INT_CONST) {
+                    let span = make_span(&offsets, j, j + 1, line_number, line_start);
+                    errors.push(Diagnostic::error(codes::INVALID_STRING_CHARACTER, format!("character `{}` cannot be used in a string constant", ch), span)
+                        .with_note(format!("string characters must have a code in the range 0..{}", MAX_INT_CONST)));
+                }
             }
             i += 1; // Consume closing quote
             tokens.push(Token {
                 token_type: TokenType::StrConst(s.clone()),
                 value: s,
-                line_number,
+                span: make_span(&offsets, start, i, line_number, line_start),
             });
             continue;
         }
@@ -110,19 +216,23 @@ pub fn tokenizer(content: &str) -> Result<Vec<Token>, String> {
                 num_str.push(chars[i]);
                 i += 1;
             }
-            let value = num_str.parse::<u16>().map_err(|e| format!("Invalid integer '{}' on line {}: {}", num_str, line_number, e))?;
-            tokens.push(Token {
-                token_type: TokenType::IntConst(value),
-                value: num_str,
-                line_number,
-            });
+            let span = make_span(&offsets, start, i, line_number, line_start);
+            let token_type = match num_str.parse::<u16>() {
+                Ok(value) if value <= MAX_INT_CONST + 1 => TokenType::IntConst(value),
+                _ => {
+                    errors.push(Diagnostic::error(codes::INVALID_INTEGER, format!("integer constant `{}` is out of range", num_str), span)
+                        .with_note(format!("integer constants must be in the range 0..{}", MAX_INT_CONST)));
+                    TokenType::Error
+                }
+            };
+            tokens.push(Token { token_type, value: num_str, span });
             continue;
         }
 
         // 6. Handle Keywords and Identifiers
-        if c.is_alphabetic() || c == '_' {
+        if c.is_ascii_alphabetic() || c == '_' {
             let mut identifier = String::new();
-            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
                 identifier.push(chars[i]);
                 i += 1;
             }
@@ -153,14 +263,33 @@ pub fn tokenizer(content: &str) -> Result<Vec<Token>, String> {
             tokens.push(Token {
                 token_type,
                 value: identifier,
-                line_number,
+                span: make_span(&offsets, start, i, line_number, line_start),
             });
             continue;
         }
 
         // 7. Handle any other character
-        return Err(format!("Invalid character '{}' on line {}", c, line_number));
+        let span = make_span(&offsets, start, i + 1, line_number, line_start);
+        let mut error = Diagnostic::error(codes::INVALID_CHARACTER, format!("invalid character `{}`", c), span);
+        if c.is_alphanumeric() {
+            error = error.with_note("identifiers are made of ASCII letters, digits and `_`");
+        }
+        errors.push(error);
+        tokens.push(Token { token_type: TokenType::Error, value: c.to_string(), span });
+        i += 1;
     }
 
-    Ok(tokens)
+    (tokens, comments, errors)
+}
+
+// Tokens never cross a line, so both ends share `line`
+fn make_span(offsets: &[usize], start: usize, end: usize, line: usize, line_start: usize) -> Span {
+    Span {
+        start: offsets[start],
+        end: offsets[end],
+        line,
+        column: start - line_start + 1,
+        end_line: line,
+        end_column: end - line_start + 1,
+    }
 }
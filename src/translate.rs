@@ -0,0 +1,307 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::Span;
+use crate::vm::{ArithmeticOp, Segment, VmCommand, VmFile};
+use std::collections::HashSet;
+
+// VM code to Hack assembly, following the nand2tetris conventions:
+//
+// - RAM[0..4] are SP, LCL, ARG, THIS and THAT, `pointer` is RAM[3..5] and
+//   `temp` is RAM[5..13]. R13 and R14 are scratch registers.
+// - Static `i` of Foo.vm is the assembler variable `Foo.i`.
+// - Label `L` in function `f` becomes `f$L`, and return addresses are `f$ret.N`.
+
+/// Where `Sys.init` is called from, for the return address the bootstrap pushes.
+const BOOTSTRAP: &str = "$bootstrap";
+
+/// Translates the files of a program into one assembly file. Every call
+/// must be to a function defined in `files`. With `bootstrap`, the code starts
+/// by setting SP to 256 and calling `Sys.init`, which must be defined too.
+/// Without it, the code starts at the first command of the first file, with
+/// the segment pointers left to whoever runs it.
+pub fn translate_program(files: &[VmFile], bootstrap: bool) -> Result<String, Box<Diagnostic>> {
+    check_calls(files, bootstrap)?;
+
+    let mut translator = Translator::default();
+    if bootstrap {
+        translator.comment("bootstrap");
+        translator.lines(&["@256", "D=A", "@SP", "M=D"]);
+        translator.function = BOOTSTRAP.to_string();
+        translator.call("Sys.init", 0);
+    }
+    for file in files {
+        translator.file(file);
+    }
+    Ok(translator.out)
+}
+
+// The assembler would take an undefined function for a variable and jump to its address
+fn check_calls(files: &[VmFile], bootstrap: bool) -> Result<(), Box<Diagnostic>> {
+    let defined: HashSet<&str> = files
+        .iter()
+        .flat_map(|file| &file.commands)
+        .filter_map(|command| match command {
+            VmCommand::Function(name, _) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut undefined: Vec<&str> = bootstrap
+        .then_some("Sys.init")
+        .into_iter()
+        .chain(files.iter().flat_map(|file| &file.commands).filter_map(|command| match command {
+            VmCommand::Call(name, _) => Some(name.as_str()),
+            _ => None,
+        }))
+        .filter(|name| !defined.contains(name))
+        .collect();
+    if undefined.is_empty() {
+        return Ok(());
+    }
+    undefined.sort();
+    undefined.dedup();
+    let names: Vec<String> = undefined.iter().map(|name| format!("`{}`", name)).collect();
+    // About the program as a whole, so there is no line to point at
+    let message = format!("the program calls undefined functions: {}", names.join(", "));
+    Err(Box::new(
+        Diagnostic::error(codes::UNDEFINED_FUNCTION, message, Span::default())
+            .with_help("the OS is not built in, copy its .vm files next to the program's sources"),
+    ))
+}
+
+#[derive(Default)]
+struct Translator {
+    out: String,
+    /// Name of the file being translated, for statics
+    file: String,
+    /// Name of the function being translated, for labels
+    function: String,
+    /// Counter that keeps generated labels unique
+    labels: usize,
+}
+
+impl Translator {
+    fn file(&mut self, file: &VmFile) {
+        self.file = file.name.clone();
+        self.function = file.name.clone();
+        for command in &file.commands {
+            self.comment(&command.to_string());
+            match command {
+                VmCommand::Arithmetic(op) => self.arithmetic(*op),
+                VmCommand::Push(segment, index) => self.push(*segment, *index),
+                VmCommand::Pop(segment, index) => self.pop(*segment, *index),
+                VmCommand::Label(label) => self.line(&format!("({}${})", self.function, label)),
+                VmCommand::Goto(label) => self.lines(&[&format!("@{}${}", self.function, label), "0;JMP"]),
+                VmCommand::IfGoto(label) => {
+                    self.pop_d();
+                    self.lines(&[&format!("@{}${}", self.function, label), "D;JNE"]);
+                }
+                VmCommand::Function(name, locals) => {
+                    self.function = name.clone();
+                    self.line(&format!("({})", name));
+                    for _ in 0..*locals {
+                        self.lines(&["@SP", "A=M", "M=0", "@SP", "M=M+1"]);
+                    }
+                }
+                VmCommand::Call(name, args) => self.call(name, *args),
+                VmCommand::Return => self.return_(),
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, op: ArithmeticOp) {
+        match op {
+            ArithmeticOp::Add => self.binary("M=D+M"),
+            ArithmeticOp::Sub => self.binary("M=M-D"),
+            ArithmeticOp::And => self.binary("M=D&M"),
+            ArithmeticOp::Or => self.binary("M=D|M"),
+            ArithmeticOp::Neg => self.lines(&["@SP", "A=M-1", "M=-M"]),
+            ArithmeticOp::Not => self.lines(&["@SP", "A=M-1", "M=!M"]),
+            ArithmeticOp::Eq => self.comparison("JEQ"),
+            ArithmeticOp::Gt => self.ordering("JGT"),
+            ArithmeticOp::Lt => self.ordering("JLT"),
+        }
+    }
+
+    // Pops y into D and leaves A at x, which `result` overwrites
+    fn binary(&mut self, result: &str) {
+        self.lines(&["@SP", "AM=M-1", "D=M", "A=A-1", result]);
+    }
+
+    // true is -1 and false is 0
+    fn comparison(&mut self, jump: &str) {
+        let end = self.unique_label("cmp");
+        self.binary("D=M-D");
+        self.lines(&["M=-1", &format!("@{}", end), &format!("D;{}", jump), "@SP", "A=M-1", "M=0", &format!("({})", end)]);
+    }
+
+    // x - y overflows when x and y have different signs, so it is only taken
+    // when they have the same sign. Otherwise x|1 has the sign x - y would have.
+    fn ordering(&mut self, jump: &str) {
+        let end = self.unique_label("cmp");
+        let [x_negative, signs_differ, signs_same, test] = ["neg", "differ", "same", "test"].map(|part| format!("{}.{}", end, part));
+        self.pop_d();
+        self.lines(&["@R13", "M=D", "@SP", "A=M-1", "D=M", &format!("@{}", x_negative), "D;JLT"]);
+        self.lines(&["@R13", "D=M", &format!("@{}", signs_differ), "D;JLT", &format!("@{}", signs_same), "0;JMP"]);
+        self.lines(&[&format!("({})", x_negative), "@R13", "D=M", &format!("@{}", signs_same), "D;JLT"]);
+        self.lines(&[&format!("({})", signs_differ), "@SP", "A=M-1", "D=M", "@1", "D=D|A", &format!("@{}", test), "0;JMP"]);
+        self.lines(&[&format!("({})", signs_same), "@R13", "D=M", "@SP", "A=M-1", "D=M-D"]);
+        self.lines(&[&format!("({})", test), "@SP", "A=M-1", "M=-1", &format!("@{}", end), &format!("D;{}", jump), "@SP", "A=M-1", "M=0"]);
+        self.line(&format!("({})", end));
+    }
+
+    fn push(&mut self, segment: Segment, index: u16) {
+        match segment {
+            Segment::Constant => self.lines(&[&format!("@{}", index), "D=A"]),
+            Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+                self.lines(&[&format!("@{}", index), "D=A", &format!("@{}", base_pointer(segment)), "A=D+M", "D=M"]);
+            }
+            Segment::Pointer | Segment::Temp | Segment::Static => {
+                let address = self.fixed_address(segment, index);
+                self.lines(&[&address, "D=M"]);
+            }
+        }
+        self.push_d();
+    }
+
+    fn pop(&mut self, segment: Segment, index: u16) {
+        match segment {
+            Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+                self.lines(&[&format!("@{}", index), "D=A", &format!("@{}", base_pointer(segment)), "D=D+M", "@R13", "M=D"]);
+                self.pop_d();
+                self.lines(&["@R13", "A=M", "M=D"]);
+            }
+            Segment::Pointer | Segment::Temp | Segment::Static => {
+                self.pop_d();
+                let address = self.fixed_address(segment, index);
+                self.lines(&[&address, "M=D"]);
+            }
+            Segment::Constant => unreachable!("the VM parser rejects `pop constant`"),
+        }
+    }
+
+    // The A-instruction for a segment that doesn't move with the call frame
+    fn fixed_address(&self, segment: Segment, index: u16) -> String {
+        match segment {
+            Segment::Pointer => format!("@{}", 3 + index),
+            Segment::Temp => format!("@{}", 5 + index),
+            _ => format!("@{}.{}", self.file, index),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: u16) {
+        let return_label = self.unique_label("ret");
+        self.lines(&[&format!("@{}", return_label), "D=A"]);
+        self.push_d();
+        for pointer in ["LCL", "ARG", "THIS", "THAT"] {
+            self.lines(&[&format!("@{}", pointer), "D=M"]);
+            self.push_d();
+        }
+        // ARG = SP - 5 - args, LCL = SP
+        self.lines(&["@SP", "D=M", &format!("@{}", 5 + args as usize), "D=D-A", "@ARG", "M=D", "@SP", "D=M", "@LCL", "M=D"]);
+        self.lines(&[&format!("@{}", name), "0;JMP", &format!("({})", return_label)]);
+    }
+
+    fn return_(&mut self) {
+        // R13 = frame, R14 = return address, read before the return value can overwrite it when there are no arguments
+        self.lines(&["@LCL", "D=M", "@R13", "M=D", "@5", "A=D-A", "D=M", "@R14", "M=D"]);
+        self.pop_d();
+        self.lines(&["@ARG", "A=M", "M=D", "@ARG", "D=M+1", "@SP", "M=D"]);
+        for pointer in ["THAT", "THIS", "ARG", "LCL"] {
+            self.lines(&["@R13", "AM=M-1", "D=M", &format!("@{}", pointer), "M=D"]);
+        }
+        self.lines(&["@R14", "A=M", "0;JMP"]);
+    }
+
+    fn push_d(&mut self) {
+        self.lines(&["@SP", "A=M", "M=D", "@SP", "M=M+1"]);
+    }
+
+    fn pop_d(&mut self) {
+        self.lines(&["@SP", "AM=M-1", "D=M"]);
+    }
+
+    fn unique_label(&mut self, kind: &str) -> String {
+        self.labels += 1;
+        format!("{}${}.{}", self.function, kind, self.labels)
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.line(&format!("// {}", text));
+    }
+
+    fn lines(&mut self, lines: &[&str]) {
+        for line in lines {
+            self.line(line);
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+fn base_pointer(segment: Segment) -> &'static str {
+    match segment {
+        Segment::Local => "LCL",
+        Segment::Argument => "ARG",
+        Segment::This => "THIS",
+        _ => "THAT",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::{assemble, tests::run_hack};
+
+    const VALUES: &[i16] = &[i16::MIN, -32767, -5, -1, 0, 1, 5, 32766, i16::MAX];
+
+    fn push(value: i16) -> String {
+        match value {
+            i16::MIN => "push constant 32767\nneg\npush constant 1\nsub\n".to_string(),
+            _ if value < 0 => format!("push constant {}\nneg\n", -value),
+            _ => format!("push constant {}\n", value),
+        }
+    }
+
+    // Translates and assembles a program made of one `Sys.vm`
+    fn build_sys(vm: &str) -> Vec<u16> {
+        let file = VmFile::parse("Sys", vm).unwrap();
+        assemble(&translate_program(&[file], true).unwrap()).unwrap().0
+    }
+
+    #[test]
+    fn comparisons_hold_for_every_pair_of_signs() {
+        let mut vm = String::from("function Sys.init 0\npush constant 3000\npop pointer 1\n");
+        let mut expected = Vec::new();
+        for &x in VALUES {
+            for &y in VALUES {
+                for (op, result) in [("lt", x < y), ("gt", x > y), ("eq", x == y)] {
+                    vm.push_str(&format!("{}{}{}\npop that {}\n", push(x), push(y), op, expected.len()));
+                    expected.push((x, op, y, if result { -1 } else { 0 }));
+                }
+            }
+        }
+        vm.push_str("label END\ngoto END\n");
+
+        let ram = run_hack(&build_sys(&vm), 200_000);
+        for (i, &(x, op, y, result)) in expected.iter().enumerate() {
+            assert_eq!(ram[3000 + i], result, "{} {} {}", x, op, y);
+        }
+    }
+
+    #[test]
+    fn most_negative_is_less_than_one() {
+        let vm = format!("function Sys.init 0\n{}{}lt\npop temp 0\n{}{}gt\npop temp 1\nlabel END\ngoto END\n", push(i16::MIN), push(1), push(i16::MIN), push(1));
+        let ram = run_hack(&build_sys(&vm), 1000);
+        assert_eq!((ram[5], ram[6]), (-1, 0));
+    }
+
+    #[test]
+    fn bootstrap_can_be_left_out() {
+        let file = VmFile::parse("Simple", "push constant 7\npush constant 8\nadd\n").unwrap();
+        assert!(translate_program(std::slice::from_ref(&file), true).unwrap_err().message.contains("`Sys.init`"));
+        let asm = translate_program(&[file], false).unwrap();
+        assert!(asm.starts_with("// push constant 7\n"));
+    }
+}
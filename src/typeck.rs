@@ -0,0 +1,387 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::parser::{
+    ClassNode, ExpressionNode, StatementNode, SubroutineCallNode, SubroutineKind, TermKind, TermNode, Type,
+};
+use crate::signatures::ProgramSignatures;
+use crate::span::Span;
+use crate::symbol_table::SymbolTable;
+use crate::tokenizer::Keyword;
+use std::fmt;
+
+/// How strictly types are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leniency {
+    /// Types must match exactly, except that `null` fits any object type.
+    Strict,
+    /// Jack as it is written in practice: `int` and `char` are interchangeable,
+    /// and `Array` converts to and from `int` and any object type.
+    Lenient,
+}
+
+/// The inferred type of an expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Int,
+    Char,
+    Boolean,
+    Class(String),
+    Null,
+    Void,
+    /// Could not be determined, e.g. an array element or an unresolved name.
+    /// Compatible with everything so that one problem isn't reported twice.
+    Unknown,
+}
+
+impl From<&Type> for Ty {
+    fn from(var_type: &Type) -> Self {
+        match var_type {
+            Type::Int => Ty::Int,
+            Type::Char => Ty::Char,
+            Type::Boolean => Ty::Boolean,
+            Type::ClassName(name) => Ty::Class(name.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Int => write!(f, "int"),
+            Ty::Char => write!(f, "char"),
+            Ty::Boolean => write!(f, "boolean"),
+            Ty::Class(name) => write!(f, "{}", name),
+            Ty::Null => write!(f, "null"),
+            Ty::Void => write!(f, "void"),
+            Ty::Unknown => write!(f, "{{unknown}}"),
+        }
+    }
+}
+
+/// Type checks every subroutine of `class` against the declared types of its
+/// variables and of the subroutines it calls.
+pub fn check_class(class: &ClassNode, signatures: &ProgramSignatures, leniency: Leniency) -> Vec<Diagnostic> {
+    // Declaration errors are reported by name resolution, which builds the same table
+    let (symbols, _) = SymbolTable::for_class_partial(class);
+    let mut checker = TypeChecker {
+        signatures,
+        leniency,
+        class_name: class.name.clone(),
+        symbols,
+        subroutine_kind: SubroutineKind::Function,
+        return_type: None,
+        errors: Vec::new(),
+    };
+
+    for subroutine in &class.subroutine_decs {
+        if checker.symbols.enter_subroutine(subroutine).is_err() {
+            continue;
+        }
+        checker.subroutine_kind = subroutine.kind.clone();
+        checker.return_type = subroutine.return_type.clone();
+        checker.check_statements(&subroutine.body.statements);
+    }
+    checker.errors
+}
+
+struct TypeChecker<'a> {
+    signatures: &'a ProgramSignatures,
+    leniency: Leniency,
+    class_name: String,
+    symbols: SymbolTable,
+    subroutine_kind: SubroutineKind,
+    return_type: Option<Type>,
+    errors: Vec<Diagnostic>,
+}
+
+impl TypeChecker<'_> {
+    fn check_statements(&mut self, statements: &[StatementNode]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &StatementNode) {
+        match statement {
+            StatementNode::Let(let_stmt) => {
+                let target = self.symbols.lookup(&let_stmt.var_name).map(|symbol| Ty::from(&symbol.var_type));
+                let value = self.infer_expression(&let_stmt.value_expr);
+                if let Some(index_expr) = &let_stmt.index_expr {
+                    if let Some(target) = &target {
+                        self.check_indexable(&let_stmt.var_name, target, let_stmt.span);
+                    }
+                    self.check_index(index_expr);
+                    // Array elements are untyped, any value can be stored
+                } else if let Some(target) = target
+                    && !self.assignable(&value, &target) {
+                    self.errors.push(Diagnostic::error(
+                        codes::TYPE_MISMATCH,
+                        format!("cannot assign a value of type `{}` to `{}` of type `{}`", value, let_stmt.var_name, target),
+                        let_stmt.value_expr.span,
+                    ));
+                }
+            }
+            StatementNode::If(if_stmt) => {
+                self.check_condition(&if_stmt.condition);
+                self.check_statements(&if_stmt.if_block);
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.check_statements(else_block);
+                }
+            }
+            StatementNode::While(while_stmt) => {
+                self.check_condition(&while_stmt.condition);
+                self.check_statements(&while_stmt.body);
+            }
+            StatementNode::Do(do_stmt) => {
+                // The result of a `do` is discarded, so a void call is fine here
+                self.infer_call(&do_stmt.call);
+            }
+            StatementNode::Return(return_stmt) => {
+                // Missing or superfluous values are a control flow question, only types are checked here
+                if let Some(value_expr) = &return_stmt.value {
+                    let value = self.infer_expression(value_expr);
+                    if let Some(return_type) = self.return_type.clone() {
+                        let expected = Ty::from(&return_type);
+                        if !self.assignable(&value, &expected) {
+                            self.errors.push(Diagnostic::error(
+                                codes::TYPE_MISMATCH,
+                                format!("expected a return value of type `{}`, found `{}`", expected, value),
+                                value_expr.span,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_condition(&mut self, condition: &ExpressionNode) {
+        let ty = self.infer_expression(condition);
+        let accepted = match ty {
+            Ty::Boolean | Ty::Unknown => true,
+            Ty::Int => self.leniency == Leniency::Lenient,
+            _ => false,
+        };
+        if !accepted {
+            self.errors.push(Diagnostic::error(
+                codes::NON_BOOLEAN_CONDITION,
+                format!("condition must be of type `boolean`, found `{}`", ty),
+                condition.span,
+            ));
+        }
+    }
+
+    fn check_index(&mut self, index_expr: &ExpressionNode) {
+        let ty = self.infer_expression(index_expr);
+        if !self.is_numeric(&ty) {
+            self.errors.push(Diagnostic::error(
+                codes::TYPE_MISMATCH,
+                format!("array index must be of type `int`, found `{}`", ty),
+                index_expr.span,
+            ));
+        }
+    }
+
+    fn check_indexable(&mut self, name: &str, ty: &Ty, span: Span) {
+        let accepted = match ty {
+            Ty::Class(class_name) if class_name == "Array" => true,
+            Ty::Unknown => true,
+            Ty::Class(_) | Ty::Int => self.leniency == Leniency::Lenient,
+            _ => false,
+        };
+        if !accepted {
+            self.errors.push(Diagnostic::error(
+                codes::NOT_AN_ARRAY,
+                format!("cannot index into `{}` of type `{}`", name, ty),
+                span,
+            ));
+        }
+    }
+
+    fn infer_expression(&mut self, expr: &ExpressionNode) -> Ty {
+        let mut ty = self.infer_term(&expr.initial_term);
+        let mut lhs_span = expr.initial_term.span;
+        for (op, term) in &expr.operations {
+            let rhs = self.infer_term(term);
+            ty = self.infer_binary(*op, (ty, lhs_span), (rhs, term.span));
+            lhs_span = lhs_span.to(term.span);
+        }
+        ty
+    }
+
+    fn infer_binary(&mut self, op: char, (lhs, lhs_span): (Ty, Span), (rhs, rhs_span): (Ty, Span)) -> Ty {
+        match op {
+            '+' | '-' | '*' | '/' => {
+                self.expect_numeric(op, &lhs, lhs_span);
+                self.expect_numeric(op, &rhs, rhs_span);
+                Ty::Int
+            }
+            '<' | '>' => {
+                self.expect_numeric(op, &lhs, lhs_span);
+                self.expect_numeric(op, &rhs, rhs_span);
+                Ty::Boolean
+            }
+            // Logical on booleans, bitwise on ints
+            '&' | '|' => match (&lhs, &rhs) {
+                // With one boolean operand, such as `flags[i] & done`, it is logical
+                (Ty::Boolean, Ty::Boolean | Ty::Unknown) | (Ty::Unknown, Ty::Boolean) => Ty::Boolean,
+                (Ty::Unknown, Ty::Unknown) => Ty::Unknown,
+                _ if self.is_numeric(&lhs) && self.is_numeric(&rhs) => Ty::Int,
+                _ => {
+                    self.errors.push(Diagnostic::error(
+                        codes::INVALID_OPERAND,
+                        format!("operator `{}` cannot be applied to `{}` and `{}`", op, lhs, rhs),
+                        lhs_span.to(rhs_span),
+                    ));
+                    Ty::Unknown
+                }
+            },
+            '=' => {
+                if !self.assignable(&lhs, &rhs) && !self.assignable(&rhs, &lhs) {
+                    self.errors.push(Diagnostic::error(
+                        codes::INVALID_OPERAND,
+                        format!("cannot compare `{}` with `{}`", lhs, rhs),
+                        lhs_span.to(rhs_span),
+                    ));
+                }
+                Ty::Boolean
+            }
+            _ => unreachable!("the parser only produces known binary operators"),
+        }
+    }
+
+    fn infer_term(&mut self, term: &TermNode) -> Ty {
+        match &term.kind {
+            TermKind::IntConst(_) => Ty::Int,
+            TermKind::StrConst(_) => Ty::Class("String".to_string()),
+            TermKind::KeywordConst(k) => match k {
+                Keyword::True | Keyword::False => Ty::Boolean,
+                Keyword::Null => Ty::Null,
+                Keyword::This if self.subroutine_kind != SubroutineKind::Function => Ty::Class(self.class_name.clone()),
+                _ => Ty::Unknown,
+            },
+            TermKind::VarName(name) => match self.symbols.lookup(name) {
+                Some(symbol) => Ty::from(&symbol.var_type),
+                None => Ty::Unknown,
+            },
+            TermKind::ArrayAccess(name, index_expr) => {
+                if let Some(symbol) = self.symbols.lookup(name) {
+                    let ty = Ty::from(&symbol.var_type);
+                    self.check_indexable(name, &ty, term.span);
+                }
+                self.check_index(index_expr);
+                Ty::Unknown
+            }
+            TermKind::SubroutineCall(call) => match self.infer_call(call) {
+                Ty::Void => {
+                    self.errors.push(Diagnostic::error(
+                        codes::VOID_VALUE,
+                        format!("`{}` returns void, its result cannot be used as a value", call.name),
+                        call.span,
+                    ));
+                    Ty::Unknown
+                }
+                ty => ty,
+            },
+            TermKind::Parenthesized(expr) => self.infer_expression(expr),
+            TermKind::UnaryOp(op, inner) => {
+                let ty = self.infer_term(inner);
+                match op {
+                    '-' => {
+                        self.expect_numeric(*op, &ty, inner.span);
+                        Ty::Int
+                    }
+                    '~' => match ty {
+                        Ty::Boolean => Ty::Boolean,
+                        _ if self.is_numeric(&ty) => ty,
+                        _ => {
+                            self.errors.push(Diagnostic::error(
+                                codes::INVALID_OPERAND,
+                                format!("operator `~` cannot be applied to `{}`", ty),
+                                inner.span,
+                            ));
+                            Ty::Unknown
+                        }
+                    },
+                    _ => unreachable!("the parser only produces known unary operators"),
+                }
+            }
+        }
+    }
+
+    // Checks the arguments against the callee's parameters and returns the callee's return type.
+    // Calls that can't be resolved are left to the name resolution pass.
+    fn infer_call(&mut self, call: &SubroutineCallNode) -> Ty {
+        let class_name = match &call.receiver {
+            None => Some(self.class_name.clone()),
+            Some(receiver) => match self.symbols.lookup(receiver).map(|symbol| symbol.var_type.clone()) {
+                Some(Type::ClassName(class_name)) => Some(class_name),
+                Some(var_type) => {
+                    self.errors.push(Diagnostic::error(
+                        codes::INVALID_RECEIVER,
+                        format!("cannot call method `{}` on `{}` of type `{}`", call.name, receiver, Ty::from(&var_type)),
+                        call.span,
+                    ));
+                    None
+                }
+                None => Some(receiver.clone()),
+            },
+        };
+
+        let args: Vec<(Ty, Span)> = call.args.iter().map(|arg| (self.infer_expression(arg), arg.span)).collect();
+        let Some(class_name) = class_name else {
+            return Ty::Unknown;
+        };
+        let Some(signature) = self.signatures.subroutine(&class_name, &call.name) else {
+            return Ty::Unknown;
+        };
+
+        for (i, ((ty, span), param)) in args.iter().zip(&signature.params).enumerate() {
+            let expected = Ty::from(param);
+            if !self.assignable(ty, &expected) {
+                self.errors.push(Diagnostic::error(
+                    codes::TYPE_MISMATCH,
+                    format!("argument {} of `{}.{}` must be of type `{}`, found `{}`", i + 1, class_name, call.name, expected, ty),
+                    *span,
+                ));
+            }
+        }
+
+        match &signature.return_type {
+            Some(return_type) => Ty::from(return_type),
+            None => Ty::Void,
+        }
+    }
+
+    fn expect_numeric(&mut self, op: char, ty: &Ty, span: Span) {
+        if !self.is_numeric(ty) {
+            self.errors.push(Diagnostic::error(
+                codes::INVALID_OPERAND,
+                format!("operator `{}` cannot be applied to `{}`", op, ty),
+                span,
+            ));
+        }
+    }
+
+    fn is_numeric(&self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Int | Ty::Unknown => true,
+            Ty::Char => self.leniency == Leniency::Lenient,
+            _ => false,
+        }
+    }
+
+    // Whether a value of type `value` may be stored where `target` is expected
+    fn assignable(&self, value: &Ty, target: &Ty) -> bool {
+        let lenient = self.leniency == Leniency::Lenient;
+        match (value, target) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+            (Ty::Void, _) | (_, Ty::Void) => false,
+            (a, b) if a == b => true,
+            (Ty::Null, Ty::Class(_)) => true,
+            (Ty::Int, Ty::Char) | (Ty::Char, Ty::Int) => lenient,
+            (Ty::Int, Ty::Class(name)) | (Ty::Class(name), Ty::Int) => lenient && name == "Array",
+            (Ty::Class(a), Ty::Class(b)) => lenient && (a == "Array" || b == "Array"),
+            _ => false,
+        }
+    }
+}
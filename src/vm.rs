@@ -0,0 +1,225 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::{code_lines, Span};
+use std::fmt;
+
+// The Hack VM language as written by `codegen`, parsed for the back ends
+// that consume it. One command per line, `//` comments, e.g.
+//
+// function Main.main 1
+// push constant 7
+// pop local 0
+// call Output.printInt 1
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Argument,
+    Local,
+    Static,
+    Constant,
+    This,
+    That,
+    Pointer,
+    Temp,
+}
+
+impl Segment {
+    pub fn parse(name: &str) -> Option<Segment> {
+        match name {
+            "argument" => Some(Segment::Argument),
+            "local" => Some(Segment::Local),
+            "static" => Some(Segment::Static),
+            "constant" => Some(Segment::Constant),
+            "this" => Some(Segment::This),
+            "that" => Some(Segment::That),
+            "pointer" => Some(Segment::Pointer),
+            "temp" => Some(Segment::Temp),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Segment::Argument => "argument",
+            Segment::Local => "local",
+            Segment::Static => "static",
+            Segment::Constant => "constant",
+            Segment::This => "this",
+            Segment::That => "that",
+            Segment::Pointer => "pointer",
+            Segment::Temp => "temp",
+        }
+    }
+
+    // `pointer` and `temp` are fixed RAM ranges, `constant` is 15 bits like a Jack integer constant
+    fn max_index(&self) -> u16 {
+        match self {
+            Segment::Pointer => 1,
+            Segment::Temp => 7,
+            Segment::Constant => 32767,
+            _ => u16::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+impl ArithmeticOp {
+    pub fn parse(name: &str) -> Option<ArithmeticOp> {
+        match name {
+            "add" => Some(ArithmeticOp::Add),
+            "sub" => Some(ArithmeticOp::Sub),
+            "neg" => Some(ArithmeticOp::Neg),
+            "eq" => Some(ArithmeticOp::Eq),
+            "gt" => Some(ArithmeticOp::Gt),
+            "lt" => Some(ArithmeticOp::Lt),
+            "and" => Some(ArithmeticOp::And),
+            "or" => Some(ArithmeticOp::Or),
+            "not" => Some(ArithmeticOp::Not),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "add",
+            ArithmeticOp::Sub => "sub",
+            ArithmeticOp::Neg => "neg",
+            ArithmeticOp::Eq => "eq",
+            ArithmeticOp::Gt => "gt",
+            ArithmeticOp::Lt => "lt",
+            ArithmeticOp::And => "and",
+            ArithmeticOp::Or => "or",
+            ArithmeticOp::Not => "not",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmCommand {
+    Arithmetic(ArithmeticOp),
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    Label(String),
+    Goto(String),
+    IfGoto(String),
+    /// A function and its number of locals
+    Function(String, u16),
+    /// A call and its number of arguments
+    Call(String, u16),
+    Return,
+}
+
+impl fmt::Display for VmCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmCommand::Arithmetic(op) => write!(f, "{}", op.as_str()),
+            VmCommand::Push(segment, index) => write!(f, "push {} {}", segment.as_str(), index),
+            VmCommand::Pop(segment, index) => write!(f, "pop {} {}", segment.as_str(), index),
+            VmCommand::Label(label) => write!(f, "label {}", label),
+            VmCommand::Goto(label) => write!(f, "goto {}", label),
+            VmCommand::IfGoto(label) => write!(f, "if-goto {}", label),
+            VmCommand::Function(name, locals) => write!(f, "function {} {}", name, locals),
+            VmCommand::Call(name, args) => write!(f, "call {} {}", name, args),
+            VmCommand::Return => write!(f, "return"),
+        }
+    }
+}
+
+/// The commands of one `.vm` file. The name, the file stem, qualifies its
+/// `static` segment.
+#[derive(Debug, Clone)]
+pub struct VmFile {
+    pub name: String,
+    pub commands: Vec<VmCommand>,
+}
+
+impl VmFile {
+    /// Parses the text of `name`.vm, stopping at the first invalid line.
+    pub fn parse(name: &str, source: &str) -> Result<VmFile, Box<Diagnostic>> {
+        let mut commands = Vec::new();
+        for (code, span) in code_lines(source) {
+            commands.push(parse_command(code, span)?);
+        }
+        Ok(VmFile { name: name.to_string(), commands })
+    }
+}
+
+fn parse_command(code: &str, span: Span) -> Result<VmCommand, Box<Diagnostic>> {
+    let words: Vec<&str> = code.split_whitespace().collect();
+    let expect_args = |count: usize| {
+        if words.len() == count + 1 {
+            Ok(())
+        } else {
+            Err(Box::new(Diagnostic::error(
+                codes::INVALID_VM_COMMAND,
+                format!("`{}` takes {} argument{}, found {}", words[0], count, if count == 1 { "" } else { "s" }, words.len() - 1),
+                span,
+            )))
+        }
+    };
+    let number = |word: &str, max: u16| {
+        word.parse::<u16>().ok().filter(|n| *n <= max).ok_or_else(|| {
+            Box::new(Diagnostic::error(codes::INVALID_VM_ARGUMENT, format!("expected a number from 0 to {}, found `{}`", max, word), span))
+        })
+    };
+    let name = |word: &str| {
+        let valid = !word.starts_with(|c: char| c.is_ascii_digit())
+            && word.chars().all(|c| c.is_ascii_alphanumeric() || "_.:$".contains(c));
+        if valid {
+            Ok(word.to_string())
+        } else {
+            Err(Box::new(Diagnostic::error(codes::INVALID_VM_ARGUMENT, format!("`{}` is not a valid label or function name", word), span)))
+        }
+    };
+
+    if let Some(op) = ArithmeticOp::parse(words[0]) {
+        expect_args(0)?;
+        return Ok(VmCommand::Arithmetic(op));
+    }
+    match words[0] {
+        "push" | "pop" => {
+            expect_args(2)?;
+            let segment = Segment::parse(words[1]).ok_or_else(|| {
+                Diagnostic::error(codes::INVALID_VM_ARGUMENT, format!("unknown segment `{}`", words[1]), span)
+                    .with_help("the segments are argument, local, static, constant, this, that, pointer and temp")
+            })?;
+            let index = number(words[2], segment.max_index())?;
+            match (words[0], segment) {
+                ("push", _) => Ok(VmCommand::Push(segment, index)),
+                (_, Segment::Constant) => Err(Diagnostic::error(codes::INVALID_VM_ARGUMENT, "cannot pop into the `constant` segment", span).into()),
+                _ => Ok(VmCommand::Pop(segment, index)),
+            }
+        }
+        "label" | "goto" | "if-goto" => {
+            expect_args(1)?;
+            let label = name(words[1])?;
+            Ok(match words[0] {
+                "label" => VmCommand::Label(label),
+                "goto" => VmCommand::Goto(label),
+                _ => VmCommand::IfGoto(label),
+            })
+        }
+        "function" | "call" => {
+            expect_args(2)?;
+            let function = name(words[1])?;
+            let count = number(words[2], u16::MAX)?;
+            Ok(if words[0] == "function" { VmCommand::Function(function, count) } else { VmCommand::Call(function, count) })
+        }
+        "return" => {
+            expect_args(0)?;
+            Ok(VmCommand::Return)
+        }
+        other => Err(Diagnostic::error(codes::INVALID_VM_COMMAND, format!("unknown VM command `{}`", other), span).into()),
+    }
+}
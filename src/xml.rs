@@ -0,0 +1,309 @@
+use crate::parser::{
+    ClassNode, ClassVarDecNode, ClassVarKind, ExpressionNode, StatementNode, SubroutineCallNode,
+    SubroutineDecNode, SubroutineKind, TermKind, TermNode, Type, VarDecNode,
+};
+use crate::tokenizer::{Keyword, Token, TokenType};
+
+// XML in the layout of the nand2tetris project 10 reference files, so that the
+// output can be checked with the course's TextComparer.
+
+/// Renders the token stream as the `<tokens>` document found in `FooT.xml`.
+pub fn tokens_to_xml(tokens: &[Token]) -> String {
+    let mut writer = XmlWriter::default();
+    writer.open("tokens");
+    // The reference token files are not indented
+    writer.indent = 0;
+    for token in tokens {
+        match &token.token_type {
+            TokenType::Keyword(k) => writer.leaf("keyword", k.as_str()),
+            TokenType::Symbol(c) => writer.leaf("symbol", &c.to_string()),
+            TokenType::IntConst(n) => writer.leaf("integerConstant", &n.to_string()),
+            TokenType::StrConst(s) => writer.leaf("stringConstant", s),
+            TokenType::Identifier(name) => writer.leaf("identifier", name),
+            // Already reported by the tokenizer, there is no XML element for it
+            TokenType::Error => {}
+        }
+    }
+    writer.close("tokens");
+    writer.out
+}
+
+/// Renders the parse tree as the `<class>` document found in `Foo.xml`.
+pub fn class_to_xml(class: &ClassNode) -> String {
+    let mut writer = XmlWriter::default();
+    writer.class(class);
+    writer.out
+}
+
+#[derive(Default)]
+struct XmlWriter {
+    out: String,
+    indent: usize,
+}
+
+impl XmlWriter {
+    fn class(&mut self, class: &ClassNode) {
+        self.open("class");
+        self.keyword(Keyword::Class);
+        self.identifier(&class.name);
+        self.symbol('{');
+        for var_dec in &class.var_decs {
+            self.class_var_dec(var_dec);
+        }
+        for subroutine in &class.subroutine_decs {
+            self.subroutine_dec(subroutine);
+        }
+        self.symbol('}');
+        self.close("class");
+    }
+
+    fn class_var_dec(&mut self, var_dec: &ClassVarDecNode) {
+        self.open("classVarDec");
+        self.keyword(match var_dec.kind {
+            ClassVarKind::Static => Keyword::Static,
+            ClassVarKind::Field => Keyword::Field,
+        });
+        self.var_type(&var_dec.var_type);
+        self.name_list(&var_dec.names);
+        self.symbol(';');
+        self.close("classVarDec");
+    }
+
+    fn subroutine_dec(&mut self, subroutine: &SubroutineDecNode) {
+        self.open("subroutineDec");
+        self.keyword(match subroutine.kind {
+            SubroutineKind::Constructor => Keyword::Constructor,
+            SubroutineKind::Function => Keyword::Function,
+            SubroutineKind::Method => Keyword::Method,
+        });
+        match &subroutine.return_type {
+            Some(return_type) => self.var_type(return_type),
+            None => self.keyword(Keyword::Void),
+        }
+        self.identifier(&subroutine.name);
+        self.symbol('(');
+        self.open("parameterList");
+        for (i, param) in subroutine.parameters.iter().enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+            self.var_type(&param.var_type);
+            self.identifier(&param.name);
+        }
+        self.close("parameterList");
+        self.symbol(')');
+
+        self.open("subroutineBody");
+        self.symbol('{');
+        for var_dec in &subroutine.body.var_decs {
+            self.var_dec(var_dec);
+        }
+        self.statements(&subroutine.body.statements);
+        self.symbol('}');
+        self.close("subroutineBody");
+        self.close("subroutineDec");
+    }
+
+    fn var_dec(&mut self, var_dec: &VarDecNode) {
+        self.open("varDec");
+        self.keyword(Keyword::Var);
+        self.var_type(&var_dec.var_type);
+        self.name_list(&var_dec.names);
+        self.symbol(';');
+        self.close("varDec");
+    }
+
+    fn statements(&mut self, statements: &[StatementNode]) {
+        self.open("statements");
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.close("statements");
+    }
+
+    fn statement(&mut self, statement: &StatementNode) {
+        match statement {
+            StatementNode::Let(let_stmt) => {
+                self.open("letStatement");
+                self.keyword(Keyword::Let);
+                self.identifier(&let_stmt.var_name);
+                if let Some(index_expr) = &let_stmt.index_expr {
+                    self.symbol('[');
+                    self.expression(index_expr);
+                    self.symbol(']');
+                }
+                self.symbol('=');
+                self.expression(&let_stmt.value_expr);
+                self.symbol(';');
+                self.close("letStatement");
+            }
+            StatementNode::If(if_stmt) => {
+                self.open("ifStatement");
+                self.keyword(Keyword::If);
+                self.symbol('(');
+                self.expression(&if_stmt.condition);
+                self.symbol(')');
+                self.block(&if_stmt.if_block);
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.keyword(Keyword::Else);
+                    self.block(else_block);
+                }
+                self.close("ifStatement");
+            }
+            StatementNode::While(while_stmt) => {
+                self.open("whileStatement");
+                self.keyword(Keyword::While);
+                self.symbol('(');
+                self.expression(&while_stmt.condition);
+                self.symbol(')');
+                self.block(&while_stmt.body);
+                self.close("whileStatement");
+            }
+            StatementNode::Do(do_stmt) => {
+                self.open("doStatement");
+                self.keyword(Keyword::Do);
+                self.subroutine_call(&do_stmt.call);
+                self.symbol(';');
+                self.close("doStatement");
+            }
+            StatementNode::Return(return_stmt) => {
+                self.open("returnStatement");
+                self.keyword(Keyword::Return);
+                if let Some(value) = &return_stmt.value {
+                    self.expression(value);
+                }
+                self.symbol(';');
+                self.close("returnStatement");
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[StatementNode]) {
+        self.symbol('{');
+        self.statements(statements);
+        self.symbol('}');
+    }
+
+    fn expression(&mut self, expr: &ExpressionNode) {
+        self.open("expression");
+        self.term(&expr.initial_term);
+        for (op, term) in &expr.operations {
+            self.symbol(*op);
+            self.term(term);
+        }
+        self.close("expression");
+    }
+
+    fn term(&mut self, term: &TermNode) {
+        self.open("term");
+        match &term.kind {
+            TermKind::IntConst(n) => self.leaf("integerConstant", &n.to_string()),
+            TermKind::StrConst(s) => self.leaf("stringConstant", s),
+            TermKind::KeywordConst(k) => self.keyword(k.clone()),
+            TermKind::VarName(name) => self.identifier(name),
+            TermKind::ArrayAccess(name, index_expr) => {
+                self.identifier(name);
+                self.symbol('[');
+                self.expression(index_expr);
+                self.symbol(']');
+            }
+            TermKind::SubroutineCall(call) => self.subroutine_call(call),
+            TermKind::Parenthesized(expr) => {
+                self.symbol('(');
+                self.expression(expr);
+                self.symbol(')');
+            }
+            TermKind::UnaryOp(op, inner) => {
+                self.symbol(*op);
+                self.term(inner);
+            }
+        }
+        self.close("term");
+    }
+
+    // A subroutine call has no element of its own, its tokens are inlined into the parent
+    fn subroutine_call(&mut self, call: &SubroutineCallNode) {
+        if let Some(receiver) = &call.receiver {
+            self.identifier(receiver);
+            self.symbol('.');
+        }
+        self.identifier(&call.name);
+        self.symbol('(');
+        self.open("expressionList");
+        for (i, arg) in call.args.iter().enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+            self.expression(arg);
+        }
+        self.close("expressionList");
+        self.symbol(')');
+    }
+
+    fn var_type(&mut self, var_type: &Type) {
+        match var_type {
+            Type::Int => self.keyword(Keyword::Int),
+            Type::Char => self.keyword(Keyword::Char),
+            Type::Boolean => self.keyword(Keyword::Boolean),
+            Type::ClassName(name) => self.identifier(name),
+        }
+    }
+
+    fn name_list(&mut self, names: &[String]) {
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                self.symbol(',');
+            }
+            self.identifier(name);
+        }
+    }
+
+    // Utility functions
+    fn keyword(&mut self, keyword: Keyword) {
+        self.leaf("keyword", keyword.as_str());
+    }
+
+    fn symbol(&mut self, symbol: char) {
+        self.leaf("symbol", &symbol.to_string());
+    }
+
+    fn identifier(&mut self, name: &str) {
+        self.leaf("identifier", name);
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.line(&format!("<{}>", tag));
+        self.indent += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.indent = self.indent.saturating_sub(1);
+        self.line(&format!("</{}>", tag));
+    }
+
+    fn leaf(&mut self, tag: &str, value: &str) {
+        self.line(&format!("<{}> {} </{}>", tag, escape(value), tag));
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
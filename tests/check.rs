@@ -0,0 +1,361 @@
+use jack_compiler::check_class;
+use jack_compiler::diagnostic::{codes, Diagnostic, Severity};
+use jack_compiler::lint::{Level, LintConfig};
+use jack_compiler::parser::Parser;
+use jack_compiler::signatures::ProgramSignatures;
+use jack_compiler::tokenizer::tokenizer;
+use jack_compiler::typeck::Leniency;
+use std::path::Path;
+
+// Checks `source` as Main.jack, in a program of it and the OS
+fn check_with(source: &str, leniency: Leniency, lints: &LintConfig) -> Vec<Diagnostic> {
+    let (tokens, errors) = tokenizer(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let (class, errors) = Parser::new(&tokens).parse_class();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let signatures = ProgramSignatures::from_classes([&class]);
+    check_class(&class, "Main", &signatures, leniency, lints)
+}
+
+fn check(source: &str) -> Vec<Diagnostic> {
+    check_with(source, Leniency::Lenient, &LintConfig::default())
+}
+
+// Only the errors, for tests that don't care about the lints
+fn errors(source: &str, leniency: Leniency) -> Vec<Diagnostic> {
+    check_with(source, leniency, &LintConfig::default()).into_iter().filter(|d| d.severity == Severity::Error).collect()
+}
+
+// Each diagnostic as its code and the line and column it points at
+fn found(diagnostics: &[Diagnostic]) -> Vec<(&str, usize, usize)> {
+    diagnostics.iter().map(|d| (d.code, d.span.line, d.span.column)).collect()
+}
+
+fn lints(levels: &[(&str, Level)]) -> LintConfig {
+    let mut config = LintConfig::default();
+    for (name, level) in levels {
+        config.set(name, *level).unwrap();
+    }
+    config
+}
+
+#[test]
+fn a_correct_class_has_no_diagnostics() {
+    let source = "\
+class Main {
+    field int count;
+
+    constructor Main new(int start) {
+        let count = start;
+        return this;
+    }
+
+    method int next() {
+        let count = count + 1;
+        return count;
+    }
+
+    function void main() {
+        var Main counter;
+        let counter = Main.new(0);
+        do Output.printInt(counter.next());
+        return;
+    }
+}
+";
+    assert_eq!(found(&check(source)), []);
+}
+
+#[test]
+fn lenient_typing_accepts_what_strict_typing_rejects() {
+    let source = "\
+class Main {
+    function void main() {
+        var char c;
+        var Array a;
+        var int i;
+        let c = 65;
+        let a = 8000;
+        if (i) {
+            let i = c + 1;
+        }
+        return;
+    }
+}
+";
+    assert_eq!(found(&errors(source, Leniency::Lenient)), []);
+    assert_eq!(
+        found(&errors(source, Leniency::Strict)),
+        [(codes::TYPE_MISMATCH, 6, 17), (codes::TYPE_MISMATCH, 7, 17), (codes::NON_BOOLEAN_CONDITION, 8, 13), (codes::INVALID_OPERAND, 9, 21)]
+    );
+}
+
+#[test]
+fn both_modes_reject_mismatched_objects() {
+    let source = "\
+class Main {
+    function void main() {
+        var String s;
+        var boolean b;
+        let s = true;
+        let b = s & 1;
+        return;
+    }
+}
+";
+    for leniency in [Leniency::Lenient, Leniency::Strict] {
+        assert_eq!(found(&errors(source, leniency)), [(codes::TYPE_MISMATCH, 5, 17), (codes::INVALID_OPERAND, 6, 17)]);
+    }
+}
+
+#[test]
+fn logical_operators_with_one_boolean_operand_are_boolean() {
+    // The element of an array has no known type
+    let source = "\
+class Main {
+    function void main() {
+        var Array flags;
+        var boolean done;
+        let done = flags[0] & done;
+        let done = done | flags[1];
+        return;
+    }
+}
+";
+    assert_eq!(found(&errors(source, Leniency::Strict)), []);
+}
+
+#[test]
+fn undefined_names_suggest_similar_ones() {
+    let source = "\
+class Main {
+    function void main() {
+        var int total;
+        let total = totl + 1;
+        do Outptu.println();
+        do Output.prinln();
+        return;
+    }
+}
+";
+    let diagnostics = errors(source, Leniency::Lenient);
+    assert_eq!(
+        found(&diagnostics),
+        [(codes::UNDEFINED_VARIABLE, 4, 21), (codes::UNDEFINED_CLASS, 5, 12), (codes::UNDEFINED_SUBROUTINE, 6, 19)]
+    );
+    assert_eq!(diagnostics[0].helps, ["a variable with a similar name exists: `total`"]);
+    assert_eq!(diagnostics[1].helps, ["a variable or class with a similar name exists: `Output`"]);
+    assert_eq!(diagnostics[2].helps, ["a subroutine with a similar name exists: `println`"]);
+}
+
+#[test]
+fn duplicate_declarations_do_not_stop_the_checks() {
+    let source = "\
+class Main {
+    field int x, y;
+    field boolean x;
+
+    method void run() {
+        let y = z;
+        return;
+    }
+
+    method void run() {
+        return;
+    }
+}
+";
+    let diagnostics = errors(source, Leniency::Lenient);
+    assert_eq!(
+        found(&diagnostics),
+        [(codes::DUPLICATE_VARIABLE, 3, 5), (codes::UNDEFINED_VARIABLE, 6, 17), (codes::DUPLICATE_SUBROUTINE, 10, 5)]
+    );
+    assert_eq!(diagnostics[2].labels[0].span.line, 5);
+}
+
+#[test]
+fn calls_are_checked_for_arity_and_kind() {
+    let source = "\
+class Main {
+    method void run() {
+        return;
+    }
+
+    function void main() {
+        var Main m;
+        do Math.abs(1, 2);
+        do Main.run();
+        do m.main();
+        do run();
+        return;
+    }
+}
+";
+    assert_eq!(
+        found(&errors(source, Leniency::Lenient)),
+        [(codes::ARGUMENT_COUNT_MISMATCH, 8, 12), (codes::INVALID_CALL_KIND, 9, 12), (codes::INVALID_CALL_KIND, 10, 12), (codes::INVALID_CALL_KIND, 11, 12)]
+    );
+}
+
+#[test]
+fn functions_cannot_use_this_or_fields() {
+    let source = "\
+class Main {
+    field Array a;
+
+    function Main make() {
+        do a.dispose(1);
+        return this;
+    }
+}
+";
+    // The call on the field is still checked
+    assert_eq!(
+        found(&check(source)),
+        [(codes::THIS_OUTSIDE_METHOD, 5, 12), (codes::ARGUMENT_COUNT_MISMATCH, 5, 12), (codes::THIS_OUTSIDE_METHOD, 6, 16)]
+    );
+}
+
+#[test]
+fn every_path_must_return() {
+    let source = "\
+class Main {
+    function int sign(int x) {
+        if (x < 0) {
+            return -1;
+        }
+    }
+
+    function int forever() {
+        while (true) {}
+    }
+
+    function int both(int x) {
+        if (x < 0) {
+            return -1;
+        } else {
+            return 1;
+        }
+    }
+}
+";
+    let diagnostics = check_with(source, Leniency::Lenient, &lints(&[("empty_body", Level::Allow)]));
+    assert_eq!(found(&diagnostics), [(codes::MISSING_RETURN, 2, 5)]);
+}
+
+#[test]
+fn returns_must_match_the_subroutine() {
+    let source = "\
+class Main {
+    field int x;
+
+    constructor Main new() {
+        let x = 0;
+        return x;
+    }
+
+    function void f() {
+        return 1;
+    }
+
+    function int g() {
+        return;
+    }
+}
+";
+    assert_eq!(
+        found(&errors(source, Leniency::Lenient)),
+        [(codes::CONSTRUCTOR_RETURN, 6, 9), (codes::TYPE_MISMATCH, 6, 16), (codes::RETURN_VALUE_IN_VOID, 10, 16), (codes::MISSING_RETURN_VALUE, 14, 9)]
+    );
+}
+
+// One source that sets off every lint once
+const LINTED: &str = "\
+class Counter {
+    field int unused;
+    field int count;
+
+    method void add(int amount, int step) {
+        var int n, left;
+        var int count;
+        let count = n;
+        if (count < 0) {}
+        return;
+        let count = 0;
+    }
+}
+";
+
+#[test]
+fn every_lint_warns_by_default() {
+    let mut names: Vec<&str> = check(LINTED).iter().inspect(|d| assert_eq!(d.severity, Severity::Warning)).map(|d| d.code).collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(
+        names,
+        [
+            "class_name_mismatch",
+            "empty_body",
+            "shadowed_field",
+            "uninitialized_local",
+            "unreachable_code",
+            "unused_field",
+            "unused_local",
+            "unused_parameter",
+        ]
+    );
+}
+
+#[test]
+fn lint_levels_can_be_set() {
+    let config = lints(&[("unused_parameter", Level::Deny), ("unused_local", Level::Allow), ("empty_body", Level::Warn)]);
+    let diagnostics = check_with(LINTED, Leniency::Lenient, &config);
+    let levels = |code: &str| diagnostics.iter().filter(|d| d.code == code).map(|d| d.severity).collect::<Vec<_>>();
+    assert_eq!(levels("unused_parameter"), [Severity::Error, Severity::Error]);
+    assert_eq!(levels("unused_local"), []);
+    assert_eq!(levels("empty_body"), [Severity::Warning]);
+}
+
+#[test]
+fn lint_levels_come_from_the_project_file() {
+    let toml = "\
+[package]
+name = \"counter\"
+
+[lints]
+unreachable_code = \"deny\"  # dead code is a bug here
+shadowed_field = \"allow\"
+";
+    let config = LintConfig::parse(Path::new("jack.toml"), toml).unwrap();
+    let diagnostics = check_with(LINTED, Leniency::Lenient, &config);
+    assert!(diagnostics.iter().any(|d| d.code == "unreachable_code" && d.severity == Severity::Error));
+    assert!(!diagnostics.iter().any(|d| d.code == "shadowed_field"));
+
+    let error = LintConfig::parse(Path::new("jack.toml"), "[lints]\nunused_locals = \"deny\"\n").unwrap_err();
+    assert_eq!(error, "jack.toml:2: unknown lint `unused_locals`");
+}
+
+#[test]
+fn locals_must_be_assigned_on_every_path_before_they_are_read() {
+    let source = "\
+class Main {
+    function int f(boolean c) {
+        var int a, b, d, e;
+        if (c) {
+            let a = 1;
+            let b = 1;
+        } else {
+            let a = 2;
+        }
+        while (c) {
+            let d = 1;
+            let c = false;
+        }
+        let e = a + b + d;
+        return e;
+    }
+}
+";
+    let diagnostics: Vec<Diagnostic> = check(source).into_iter().filter(|d| d.code == "uninitialized_local").collect();
+    assert_eq!(found(&diagnostics), [("uninitialized_local", 14, 21), ("uninitialized_local", 14, 25)]);
+}
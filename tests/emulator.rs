@@ -0,0 +1,235 @@
+use jack_compiler::compile_program;
+use jack_compiler::diagnostic::codes;
+use jack_compiler::emulator::{Emulator, Stop};
+use jack_compiler::vm::VmFile;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Console output the test can read after the emulator took ownership of the writer
+#[derive(Clone, Default)]
+struct Console(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn run(sources: &[(&str, &str)], input: &'static str) -> (Emulator, Result<Stop, String>, String) {
+    let files = compile_program(sources).unwrap();
+    let mut emulator = Emulator::new(&files).unwrap();
+    let console = Console::default();
+    emulator.set_console(Box::new(input.as_bytes()), Box::new(console.clone()));
+    emulator.start("Sys.init").unwrap();
+    let result = emulator.run(Some(1_000_000));
+    let output = String::from_utf8(console.0.borrow().clone()).unwrap();
+    (emulator, result, output)
+}
+
+#[test]
+fn runs_a_program_with_objects_and_recursion() {
+    let main = "\
+class Main {
+    function void main() {
+        var Point p;
+        let p = Point.new(3, -4);
+        do Memory.poke(8000, p.manhattan());
+        do Memory.poke(8001, Main.factorial(7));
+        if (-32768 < 1) {
+            do Memory.poke(8002, -1);
+        }
+        do Output.printString(\"sum: \");
+        do Output.printInt(p.manhattan());
+        do Output.println();
+        return;
+    }
+
+    function int factorial(int n) {
+        if (n < 2) {
+            return 1;
+        }
+        return n * Main.factorial(n - 1);
+    }
+}
+";
+    let point = "\
+class Point {
+    field int x, y;
+
+    constructor Point new(int ax, int ay) {
+        let x = ax;
+        let y = ay;
+        return this;
+    }
+
+    method int manhattan() {
+        return Math.abs(x) + Math.abs(y);
+    }
+}
+";
+    let (emulator, result, output) = run(&[("Main", main), ("Point", point)], "");
+    assert_eq!(result, Ok(Stop::Halted));
+    assert_eq!(&emulator.ram()[8000..8003], [7, 5040, -1]);
+    assert_eq!(output, "sum: 7\n");
+}
+
+#[test]
+fn reads_from_the_console() {
+    let main = "\
+class Main {
+    function void main() {
+        var int n;
+        let n = Keyboard.readInt(\"n? \");
+        do Memory.poke(8000, n * 2);
+        return;
+    }
+}
+";
+    let (emulator, result, output) = run(&[("Main", main)], "21\n");
+    assert_eq!(result, Ok(Stop::Halted));
+    assert_eq!(emulator.ram()[8000], 42);
+    assert_eq!(output, "n? ");
+}
+
+#[test]
+fn output_is_drawn_on_the_screen() {
+    let main = "\
+class Main {
+    function void main() {
+        do Output.printChar(73);
+        return;
+    }
+}
+";
+    let (emulator, _, _) = run(&[("Main", main)], "");
+    // The top rows of `I` in the Jack OS font, in the low byte of the first word of each pixel row
+    let rows: Vec<i16> = (0..3).map(|row| emulator.ram()[16384 + row * 32]).collect();
+    assert_eq!(rows, [30, 12, 12]);
+}
+
+#[test]
+fn freed_blocks_are_reused() {
+    let main = "\
+class Main {
+    function void main() {
+        var Array a, b;
+        let a = Array.new(10);
+        do a.dispose();
+        let b = Array.new(10);
+        if (a = b) {
+            do Memory.poke(8000, -1);
+        }
+        return;
+    }
+}
+";
+    let (emulator, result, _) = run(&[("Main", main)], "");
+    assert_eq!(result, Ok(Stop::Halted));
+    assert_eq!(emulator.ram()[8000], -1);
+}
+
+#[test]
+fn runtime_errors_name_the_calls_that_led_to_them() {
+    let main = "\
+class Main {
+    function void main() {
+        do Main.countdown(5);
+        return;
+    }
+
+    function int countdown(int n) {
+        return 100 / n + Main.countdown(n - 1);
+    }
+}
+";
+    let (_, result, _) = run(&[("Main", main)], "");
+    assert_eq!(
+        result.unwrap_err(),
+        "division by zero (OS error 3) in `Math.divide`, called from `Main.countdown` (6 frames), `Main.main`"
+    );
+}
+
+#[test]
+fn deep_recursion_is_summarized() {
+    let main = "\
+class Main {
+    function void main() {
+        do Main.forever(0);
+        return;
+    }
+
+    function int forever(int n) {
+        return Main.forever(n + 1);
+    }
+}
+";
+    let (_, result, _) = run(&[("Main", main)], "");
+    let error = result.unwrap_err();
+    assert!(error.starts_with("stack overflow in `Main.forever` ("), "{}", error);
+    assert!(error.ends_with("frames), called from `Main.main`"), "{}", error);
+}
+
+#[test]
+fn characters_the_vm_cannot_push_fail_to_compile() {
+    let main = "class Main { function void main() { do Output.printString(\"\u{1F600}\"); return; } }";
+    let failures = compile_program(&[("Main", main)]).unwrap_err();
+    assert_eq!(failures[0].1[0].code, codes::INVALID_STRING_CHARACTER);
+}
+
+#[test]
+fn a_stack_pointer_outside_the_stack_is_an_error() {
+    // Writes -5 to SP through `that`, then pops
+    let sys = "\
+function Sys.init 0
+push constant 0
+pop pointer 1
+push constant 5
+neg
+pop that 0
+pop temp 0
+";
+    let mut emulator = Emulator::new(&[VmFile::parse("Sys", sys).unwrap()]).unwrap();
+    emulator.start("Sys.init").unwrap();
+    let error = emulator.run(Some(100)).unwrap_err();
+    assert_eq!(error, "the stack pointer was set to -5, outside the stack in `Sys.init`");
+}
+
+#[test]
+fn os_functions_check_the_stack_pointer() {
+    let sys = "\
+function Sys.init 0
+push constant 0
+pop pointer 1
+push constant 5
+neg
+pop that 0
+call Math.abs 1
+";
+    let mut emulator = Emulator::new(&[VmFile::parse("Sys", sys).unwrap()]).unwrap();
+    emulator.start("Sys.init").unwrap();
+    let error = emulator.run(Some(100)).unwrap_err();
+    assert_eq!(error, "the stack pointer was set to -5, outside the stack in `Sys.init`");
+}
+
+#[test]
+fn erases_a_key_that_was_read() {
+    let main = "\
+class Main {
+    function void main() {
+        var char c;
+        let c = Keyboard.readChar();
+        do Output.backSpace();
+        do Output.printChar(c + 1);
+        return;
+    }
+}
+";
+    let (_, result, output) = run(&[("Main", main)], "x");
+    assert_eq!(result, Ok(Stop::Halted));
+    assert_eq!(output, "\u{8} \u{8}y");
+}
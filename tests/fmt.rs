@@ -0,0 +1,56 @@
+use jack_compiler::parser::Parser;
+use jack_compiler::pretty::format_class;
+use jack_compiler::tokenizer::{comments, tokenizer};
+
+fn fmt(source: &str) -> String {
+    let (tokens, errors) = tokenizer(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let (class, errors) = Parser::new(&tokens).parse_class();
+    assert!(errors.is_empty(), "{:?}", errors);
+    format_class(&class, &comments(source))
+}
+
+const MESSY: &str = "\
+// Counts things
+class Main {
+  field int count; // how many
+  /** Adds one
+    * to the count */
+  method void add( ) {
+    let count=count+1;  // step
+    if (count>10) { let count=0; } else { do Output.printInt(count); }
+    // done
+    return;
+  }
+}
+";
+
+#[test]
+fn keeps_line_and_block_comments() {
+    let expected = "\
+// Counts things
+class Main {
+    field int count; // how many
+
+    /** Adds one
+     * to the count */
+    method void add() {
+        let count = count + 1; // step
+        if (count > 10) {
+            let count = 0;
+        } else {
+            do Output.printInt(count);
+        }
+        // done
+        return;
+    }
+}
+";
+    assert_eq!(fmt(MESSY), expected);
+}
+
+#[test]
+fn formatting_twice_changes_nothing() {
+    let once = fmt(MESSY);
+    assert_eq!(fmt(&once), once);
+}
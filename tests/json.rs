@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// A directory of its own under the system temp directory for each test
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("jack-compiler-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        fs::write(dir.join(file), content).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn prints_a_record_per_diagnostic_and_a_summary() {
+    let main = "\
+class Main {
+    function void main() {
+        var int x;
+        let y = 1;
+        return;
+    }
+}
+";
+    let util = "\
+class Util {
+    function int one() {
+        return 1;
+    }
+}
+";
+    let dir = project("json", &[("Main.jack", main), ("Util.jack", util)]);
+    let output = Command::new(env!("CARGO_BIN_EXE_jack-compiler")).args(["check", "--message-format=json"]).arg(&dir).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    let file = dir.join("Main.jack").display().to_string();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            format!(
+                "{{\"type\":\"diagnostic\",\"file\":\"{}\",\"severity\":\"warning\",\"code\":\"unused_local\",\"message\":\"unused local variable `x`\",\
+                 \"span\":{{\"start\":48,\"end\":58,\"line\":3,\"column\":9,\"end_line\":3,\"end_column\":19}},\
+                 \"labels\":[],\"notes\":[],\"suggestions\":[],\"expected\":[]}}",
+                file
+            ),
+            format!(
+                "{{\"type\":\"diagnostic\",\"file\":\"{}\",\"severity\":\"error\",\"code\":\"E0200\",\"message\":\"cannot find variable `y` in class `Main`\",\
+                 \"span\":{{\"start\":71,\"end\":72,\"line\":4,\"column\":13,\"end_line\":4,\"end_column\":14}},\
+                 \"labels\":[],\"notes\":[],\"suggestions\":[\"a variable with a similar name exists: `x`\"],\"expected\":[]}}",
+                file
+            ),
+            "{\"type\":\"summary\",\"files\":2,\"succeeded\":1,\"failed\":1,\"errors\":1,\"warnings\":1,\"success\":false}".to_string(),
+        ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prints_only_the_summary_for_a_clean_program() {
+    let dir = project("json-clean", &[("Main.jack", "class Main {\n    function void main() {\n        return;\n    }\n}\n")]);
+    let output = Command::new(env!("CARGO_BIN_EXE_jack-compiler")).args(["check", "--message-format=json"]).arg(&dir).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"type\":\"summary\",\"files\":1,\"succeeded\":1,\"failed\":0,\"errors\":0,\"warnings\":0,\"success\":true}\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
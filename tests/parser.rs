@@ -0,0 +1,120 @@
+use jack_compiler::codegen::CodeGenerator;
+use jack_compiler::diagnostic::{codes, Diagnostic};
+use jack_compiler::parser::{ClassNode, Parser};
+use jack_compiler::tokenizer::tokenizer;
+
+fn parse(source: &str) -> (ClassNode, Vec<Diagnostic>) {
+    let (tokens, errors) = tokenizer(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    Parser::new(&tokens).parse_class()
+}
+
+// Each diagnostic as its code, message and the line and column it points at
+fn found(diagnostics: &[Diagnostic]) -> Vec<(&str, &str, usize, usize)> {
+    diagnostics.iter().map(|d| (d.code, d.message.as_str(), d.span.line, d.span.column)).collect()
+}
+
+#[test]
+fn reports_every_syntax_error_in_a_file() {
+    let source = "\
+class Main {
+    function void main() {
+        var int x
+        let x = 1 +;
+        do Output.printInt(x;
+        return;
+    }
+
+    function void other() {
+        let x = ;
+        return;
+    }
+}
+";
+    let (class, errors) = parse(source);
+    assert_eq!(
+        found(&errors),
+        [
+            (codes::UNEXPECTED_TOKEN, "expected `;`, found keyword `let`", 3, 18),
+            (codes::UNEXPECTED_TOKEN, "expected term, found `;`", 4, 20),
+            (codes::UNEXPECTED_TOKEN, "expected `)`, found `;`", 5, 29),
+            (codes::UNEXPECTED_TOKEN, "expected term, found `;`", 10, 17),
+        ]
+    );
+    // Both subroutines are still in the tree
+    let names: Vec<&str> = class.subroutine_decs.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["main", "other"]);
+}
+
+#[test]
+fn reports_one_error_for_each_broken_statement() {
+    // After an error the parser skips to the next statement, so the rest of
+    // a broken statement isn't reported again
+    let source = "\
+class Main {
+    function void main() {
+        let = = = 1;
+        do ) ) );
+        while (true {
+            return;
+        }
+        return;
+    }
+}
+";
+    let (_, errors) = parse(source);
+    assert_eq!(
+        found(&errors),
+        [
+            (codes::UNEXPECTED_TOKEN, "expected identifier, found `=`", 3, 13),
+            (codes::UNEXPECTED_TOKEN, "expected identifier, found `)`", 4, 12),
+            (codes::UNEXPECTED_TOKEN, "expected `)`, found `{`", 5, 21),
+        ]
+    );
+}
+
+#[test]
+fn reports_a_file_that_ends_inside_a_class() {
+    let (_, errors) = parse("class Main {\n    function void main() {\n        return;\n");
+    assert_eq!(found(&errors), [(codes::UNEXPECTED_EOF, "expected `}`, found end of file", 3, 16)]);
+}
+
+#[test]
+fn integer_constants_range_from_0_to_32767() {
+    let source = "\
+class Main {
+    function void main() {
+        var int x;
+        let x = 32767;
+        let x = -32768;
+        let x = 32768;
+        let x = 1 - 32768;
+        return;
+    }
+}
+";
+    // 32768 only parses after a unary minus
+    let (_, errors) = parse(source);
+    assert_eq!(
+        found(&errors),
+        [
+            (codes::INVALID_INTEGER, "integer constant `32768` is out of range", 6, 17),
+            (codes::INVALID_INTEGER, "integer constant `32768` is out of range", 7, 21),
+        ]
+    );
+}
+
+#[test]
+fn minus_32768_compiles_to_a_constant_the_vm_accepts() {
+    let source = "\
+class Main {
+    function int min() {
+        return -32768;
+    }
+}
+";
+    let (class, errors) = parse(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let vm_code = CodeGenerator::new().compile_class(&class).unwrap();
+    assert_eq!(vm_code, "function Main.min 0\npush constant 32767\nneg\npush constant 1\nsub\nreturn\n");
+}
@@ -0,0 +1,80 @@
+use jack_compiler::diagnostic::{codes, Diagnostic};
+use jack_compiler::json::Json;
+use jack_compiler::lint::{Level, LintConfig};
+use jack_compiler::sarif::log;
+use jack_compiler::span::Span;
+use std::path::Path;
+
+// The value of `key` in an object
+fn field<'a>(json: &'a Json, key: &str) -> &'a Json {
+    match json {
+        Json::Object(fields) => &fields.iter().find(|(k, _)| *k == key).unwrap_or_else(|| panic!("no `{}` in {}", key, json)).1,
+        _ => panic!("{} is not an object", json),
+    }
+}
+
+fn items(json: &Json) -> &[Json] {
+    match json {
+        Json::Array(items) => items,
+        _ => panic!("{} is not an array", json),
+    }
+}
+
+fn span(line: usize, column: usize, start: usize, len: usize) -> Span {
+    Span { start, end: start + len, line, column, end_line: line, end_column: column + len }
+}
+
+#[test]
+fn writes_a_sarif_2_1_0_log() {
+    let error = Diagnostic::error(codes::DUPLICATE_VARIABLE, "variable `x` is already declared in this scope", span(3, 9, 40, 10))
+        .with_label(span(2, 9, 20, 10), "previous declaration here")
+        .with_help("rename one of them");
+    let warning = Diagnostic::warning("unused_local", "unused local variable `y`", span(4, 9, 60, 10));
+    let diagnostics = [error, warning];
+    let mut lints = LintConfig::default();
+    lints.set("unused_local", Level::Deny).unwrap();
+    let log = log(&[(Path::new("src/Main.jack"), &diagnostics)], &lints);
+
+    assert_eq!(field(&log, "version").to_string(), "\"2.1.0\"");
+    assert_eq!(field(&log, "$schema").to_string(), "\"https://json.schemastore.org/sarif-2.1.0.json\"");
+    let runs = items(field(&log, "runs"));
+    assert_eq!(runs.len(), 1);
+
+    // Every code and lint is a rule, lints at their configured level
+    let rules = items(field(field(field(&runs[0], "tool"), "driver"), "rules"));
+    let rule = |id: &str| rules.iter().position(|rule| field(rule, "id").to_string() == format!("\"{}\"", id)).unwrap();
+    assert_eq!(field(&rules[rule("unused_local")], "defaultConfiguration").to_string(), "{\"level\":\"error\"}");
+
+    let results = items(field(&runs[0], "results"));
+    assert_eq!(results.len(), 2);
+    assert_eq!(field(&results[0], "ruleId").to_string(), "\"E0201\"");
+    assert_eq!(field(&results[0], "ruleIndex").to_string(), rule(codes::DUPLICATE_VARIABLE).to_string());
+    assert_eq!(field(&results[0], "level").to_string(), "\"error\"");
+    assert_eq!(
+        field(&results[0], "message").to_string(),
+        "{\"text\":\"variable `x` is already declared in this scope\\nhelp: rename one of them\"}"
+    );
+    let locations = items(field(&results[0], "locations"));
+    assert_eq!(
+        locations[0].to_string(),
+        "{\"physicalLocation\":{\"artifactLocation\":{\"uri\":\"src/Main.jack\",\"uriBaseId\":\"%SRCROOT%\"},\
+         \"region\":{\"startLine\":3,\"startColumn\":9,\"endLine\":3,\"endColumn\":19,\"byteOffset\":40,\"byteLength\":10}}}"
+    );
+    let related = items(field(&results[0], "relatedLocations"));
+    assert_eq!(field(&related[0], "message").to_string(), "{\"text\":\"previous declaration here\"}");
+    assert_eq!(field(&results[1], "ruleId").to_string(), "\"unused_local\"");
+    assert_eq!(field(&results[1], "level").to_string(), "\"warning\"");
+}
+
+#[test]
+fn percent_encodes_artifact_uris() {
+    let diagnostics = [Diagnostic::error(codes::UNDEFINED_FUNCTION, "no function", Span::default())];
+    let uri = |path: &str| {
+        let log = log(&[(Path::new(path), &diagnostics)], &LintConfig::default());
+        let result = &items(field(&items(field(&log, "runs"))[0], "results"))[0];
+        let location = field(field(&items(field(result, "locations"))[0], "physicalLocation"), "artifactLocation");
+        field(location, "uri").to_string()
+    };
+    assert_eq!(uri("./my games/Pong#2/Main.jack"), "\"my%20games/Pong%232/Main.jack\"");
+    assert_eq!(uri("/tmp/100%/Main.jack"), "\"file:///tmp/100%25/Main.jack\"");
+}
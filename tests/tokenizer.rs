@@ -0,0 +1,52 @@
+use jack_compiler::diagnostic::codes;
+use jack_compiler::tokenizer::{tokenizer, TokenType};
+
+#[test]
+fn reports_every_lexical_error_and_keeps_going() {
+    let source = "let x = 1 # 2;\nlet s = \"abc\nlet y = $;\n/* open";
+    let (tokens, errors) = tokenizer(source);
+    let found: Vec<(&str, usize, usize)> = errors.iter().map(|e| (e.code, e.span.line, e.span.column)).collect();
+    assert_eq!(
+        found,
+        [
+            (codes::INVALID_CHARACTER, 1, 11),
+            (codes::UNTERMINATED_STRING, 2, 9),
+            (codes::INVALID_CHARACTER, 3, 9),
+            (codes::UNTERMINATED_COMMENT, 4, 1),
+        ]
+    );
+
+    // The bad text is kept as error tokens, and the tokens after it are still
+    // there. An unclosed comment is dropped like any other comment.
+    let errors: Vec<&str> = tokens.iter().filter(|t| t.token_type == TokenType::Error).map(|t| t.value.as_str()).collect();
+    assert_eq!(errors, ["#", "\"abc", "$"]);
+    let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+    assert_eq!(
+        values,
+        ["let", "x", "=", "1", "#", "2", ";", "let", "s", "=", "\"abc", "let", "y", "=", "$", ";"]
+    );
+}
+
+#[test]
+fn valid_input_has_no_error_tokens() {
+    let (tokens, errors) = tokenizer("class Main { field String s; /* done */ }\n");
+    assert!(errors.is_empty());
+    assert!(tokens.iter().all(|t| t.token_type != TokenType::Error));
+    assert_eq!(tokens[4].token_type, TokenType::Identifier("String".to_string()));
+}
+
+#[test]
+fn integer_constants_above_32768_are_lexical_errors() {
+    // 32768 is left to the parser, which accepts it only in `-32768`
+    let (tokens, errors) = tokenizer("32767 32768 32769 100000");
+    let types: Vec<&TokenType> = tokens.iter().map(|t| &t.token_type).collect();
+    assert_eq!(types, [&TokenType::IntConst(32767), &TokenType::IntConst(32768), &TokenType::Error, &TokenType::Error]);
+    let found: Vec<(&str, &str, usize)> = errors.iter().map(|e| (e.code, e.message.as_str(), e.span.column)).collect();
+    assert_eq!(
+        found,
+        [
+            (codes::INVALID_INTEGER, "integer constant `32769` is out of range", 13),
+            (codes::INVALID_INTEGER, "integer constant `100000` is out of range", 19),
+        ]
+    );
+}